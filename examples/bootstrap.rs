@@ -0,0 +1,91 @@
+// 法を使い切った暗号文をブートストラップする
+#![allow(incomplete_features)]
+#![feature(generic_const_exprs)]
+
+use ckks_rs_playground::ckks::{
+    self,
+    evaluator::Evaluator,
+    keys::{EvaluationKey, PublicKey, SecretKey},
+    params::Params,
+};
+use num_complex::Complex64;
+
+macro_rules! measure {
+    ($name:expr, $body:block) => {{
+        let start = std::time::Instant::now();
+        let result = $body;
+        let duration = start.elapsed();
+        println!("[{}, {:.2?}]\nresult: {:.3?}\n", $name, duration, result);
+        result
+    }};
+}
+
+fn main() {
+    const N: usize = 8;
+    // q_L * P < 2^127 に収める
+    const LIMIT: u32 = 9;
+    const P: i128 = 1 << 11;
+    const Q0: i128 = 1 << 14;
+    const SCALE: i128 = 1 << 12;
+
+    let params = Params::new(LIMIT, P, Q0, SCALE);
+    let ql = params.modulo(LIMIT);
+
+    let z = [
+        Complex64::new(0.5, -0.25),
+        Complex64::new(-0.75, 0.1),
+        Complex64::new(0.3, 0.6),
+        Complex64::new(-0.2, -0.9),
+    ];
+
+    let (public_key, secret_key, evaluation_key, evaluator) = measure!("Generate keys", {
+        let secret_key = SecretKey::<i128, N>::generate(ql);
+        let public_key = PublicKey::generate(&secret_key, ql);
+        let evaluation_key = EvaluationKey::generate(&secret_key, ql, SCALE);
+        let elements: Vec<usize> = (3..2 * N).step_by(2).collect();
+        let galois_keys = ckks::generate_galois_keys(&secret_key, &params, &elements);
+        (
+            public_key,
+            secret_key,
+            evaluation_key,
+            Evaluator::new(params, galois_keys),
+        )
+    });
+
+    let exhaust = |z: &[Complex64]| {
+        let plaintext = ckks::encode::<i128, N>(z, P as f64);
+        let mut ciphertext = ckks::encrypt(plaintext, public_key, evaluation_key);
+        while evaluator.level(&ciphertext) > 0 {
+            ciphertext = evaluator.mul_const(ciphertext, 1.0);
+        }
+        ciphertext
+    };
+
+    let ciphertext = measure!("Encrypt and multiply by 1 until q0", { exhaust(&z) });
+    measure!("Level before bootstrapping", {
+        evaluator.level(&ciphertext)
+    });
+
+    let bootstrapped = measure!("Bootstrap", { evaluator.bootstrap(&ciphertext) });
+    measure!("Level after bootstrapping", {
+        evaluator.level(&bootstrapped)
+    });
+
+    let decoded = measure!("Decrypt and decode", {
+        ckks::decode(ckks::decrypt(bootstrapped, &secret_key))
+    });
+    measure!("Precision (bits)", { ckks::precision_bits(&z, &decoded) });
+
+    // 2 スロットだけ使う場合は EvalMod が1回で済む
+    let sparse = &z[..2];
+    let ciphertext = measure!("Encrypt 2 slots and multiply by 1 until q0", {
+        exhaust(sparse)
+    });
+    let bootstrapped = measure!("Bootstrap (sparse)", { evaluator.bootstrap(&ciphertext) });
+    let decoded = measure!("Decrypt and decode (sparse)", {
+        ckks::decode(ckks::decrypt(bootstrapped, &secret_key))
+    });
+    measure!("Precision (bits, sparse)", {
+        ckks::precision_bits(sparse, &decoded)
+    });
+}
@@ -0,0 +1,83 @@
+// 暗号文のまま大小比較と max/min を計算する
+#![allow(incomplete_features)]
+#![feature(generic_const_exprs)]
+
+use ckks_rs_playground::ckks::{
+    self,
+    evaluator::Evaluator,
+    keys::{EvaluationKey, PublicKey, SecretKey},
+    params::Params,
+};
+use num_complex::Complex64;
+
+macro_rules! measure {
+    ($name:expr, $body:block) => {{
+        let start = std::time::Instant::now();
+        let result = $body;
+        let duration = start.elapsed();
+        println!("[{}, {:.2?}]\nresult: {:.3?}\n", $name, duration, result);
+        result
+    }};
+}
+
+fn main() {
+    const N: usize = 8;
+    // q_L * P < 2^127 に収める
+    const LIMIT: u32 = 6;
+    const P: i128 = 1 << 16;
+    const Q0: i128 = 1 << 18;
+    const SCALE: i128 = 1 << 12;
+
+    let params = Params::new(LIMIT, P, Q0, SCALE);
+    let ql = params.modulo(LIMIT);
+
+    let a = [0.9, 0.1, 0.7, 0.3];
+    let b = [0.2, 0.6, 0.3, 0.8];
+
+    let (public_key, secret_key, evaluation_key, evaluator) = measure!("Generate keys", {
+        let secret_key = SecretKey::<i128, N>::generate(ql);
+        let public_key = PublicKey::generate(&secret_key, ql);
+        let evaluation_key = EvaluationKey::generate(&secret_key, ql, SCALE);
+        (
+            public_key,
+            secret_key,
+            evaluation_key,
+            Evaluator::<i128, N>::new(params, vec![]),
+        )
+    });
+    let encrypt = |x: [f64; N / 2]| {
+        let plaintext = ckks::encode::<i128, N>(&x.map(|x| Complex64::new(x, 0.0)), P as f64);
+        ckks::encrypt(plaintext, public_key, evaluation_key)
+    };
+    let decrypt = |ciphertext| {
+        ckks::decode(ckks::decrypt(ciphertext, &secret_key))
+            .iter()
+            .map(|z| z.re)
+            .collect::<Vec<_>>()
+    };
+    let (ca, cb) = measure!("Encrypt", { (encrypt(a), encrypt(b)) });
+
+    measure!("sign(a - b)", {
+        decrypt(evaluator.sign(&(ca.clone() - cb.clone())))
+    });
+    measure!("sign (expected)", {
+        std::array::from_fn::<_, 4, _>(|i| (a[i] - b[i]).signum())
+    });
+    measure!("Depth of sign", { evaluator.sign_config.depth() });
+    measure!("compare(a, b)", { decrypt(evaluator.compare(&ca, &cb)) });
+
+    // max/min は sign より1段深い
+    let mut evaluator = evaluator;
+    evaluator.sign_config.n = 1;
+    measure!("Depth of sign for max/min", {
+        evaluator.sign_config.depth() + 1
+    });
+    measure!("max(a, b)", { decrypt(evaluator.max(&ca, &cb)) });
+    measure!("max (expected)", {
+        std::array::from_fn::<_, 4, _>(|i| a[i].max(b[i]))
+    });
+    measure!("min(a, b)", { decrypt(evaluator.min(&ca, &cb)) });
+    measure!("min (expected)", {
+        std::array::from_fn::<_, 4, _>(|i| a[i].min(b[i]))
+    });
+}
@@ -2,7 +2,14 @@
 #![allow(incomplete_features)]
 #![feature(generic_const_exprs)]
 
-use ckks_rs_playground::ckks;
+use ckks_rs_playground::ckks::{
+    self,
+    compress::{compress_for_decryption, decrypt_compressed},
+    encoder::Encoder,
+    evaluator::Evaluator,
+    keys::KeySet,
+    params::Params,
+};
 use num_complex::Complex64;
 
 macro_rules! measure {
@@ -32,27 +39,41 @@ fn main() {
         [Complex64::new(20.0, 0.0), Complex64::new(30.3, 55.0)]
     });
 
-    let plaintext = measure!("Encode plaintext", { ckks::encode::<i64, N>(z, DELTA) });
+    let plaintext = measure!("Encode plaintext", {
+        ckks::encode::<i64, N>(&z, DELTA as f64)
+    });
     let plaintext_decoded = measure!("Decode plaintext", { ckks::decode(plaintext) });
     measure!("diff (noop)", { diff(&z, &plaintext_decoded) });
 
     let (public_key, secret_key, evaluation_key) = measure!("Generate keys", {
         ckks::generate_keys(LIMIT, P, Q0, SCALE)
     });
+    measure!("Validate keys", {
+        KeySet {
+            params: Params::new(LIMIT, P, Q0, SCALE),
+            public_key,
+            evaluation_key,
+            galois_keys: vec![],
+        }
+        .validate(&secret_key)
+        .unwrap()
+    });
 
     let ciphertext = measure!("Encrypt plaintext", {
         ckks::encrypt(plaintext, public_key, evaluation_key)
     });
 
     let decrypted = measure!("Decrypt ciphertext", {
-        ckks::decrypt(ciphertext, secret_key)
+        ckks::decrypt(ciphertext.clone(), &secret_key)
     });
     measure!("diff (encdec)", { diff(&z, &ckks::decode(decrypted)) });
 
     let plaintext_added = measure!("Add plaintexts", { plaintext + plaintext });
-    let ciphertext_added = measure!("Add ciphertexts", { ciphertext + ciphertext });
+    let ciphertext_added = measure!("Add ciphertexts", {
+        ciphertext.clone() + ciphertext.clone()
+    });
     let decrypted_added = measure!("Decrypt added ciphertext", {
-        ckks::decrypt(ciphertext_added, secret_key)
+        ckks::decrypt(ciphertext_added, &secret_key)
     });
     let z_added = measure!("Add complex vectors", { z.map(|c| c * 2.0) });
     let plaintext_added_decoded =
@@ -63,9 +84,11 @@ fn main() {
     measure!("diff (add)", { diff(&z_added, &decrypted_added_decoded) });
 
     let plaintext_multiplied = measure!("Multiply plaintexts", { plaintext * plaintext });
-    let ciphertext_multiplied = measure!("Multiply ciphertexts", { ciphertext * ciphertext });
+    let ciphertext_multiplied = measure!("Multiply ciphertexts", {
+        ciphertext.clone() * ciphertext.clone()
+    });
     let decrypted_multiplied = measure!("Decrypt multiplied ciphertext", {
-        ckks::decrypt(ciphertext_multiplied, secret_key)
+        ckks::decrypt(ciphertext_multiplied, &secret_key)
     });
     let z_multiplied = measure!("Multiply complex vectors", { z.map(|c| c * c) });
     let plaintext_multiplied_decoded = measure!("Decode multiplied plaintext", {
@@ -80,4 +103,69 @@ fn main() {
     measure!("diff (mul)", {
         diff(&z_multiplied, &decrypted_multiplied_decoded)
     });
+
+    let ciphertext_multiplied = ciphertext.clone() * ciphertext.clone();
+    let compressed = measure!("Compress multiplied ciphertext", {
+        compress_for_decryption(&ciphertext_multiplied, 10)
+    });
+    measure!("Ciphertext size (bytes, raw vs compressed)", {
+        (
+            ciphertext_multiplied.c.len() * N * size_of::<i64>(),
+            compressed.size(),
+        )
+    });
+    let decrypted_compressed_decoded = measure!("Decrypt compressed ciphertext", {
+        ckks::decode(decrypt_compressed(&compressed, &secret_key))
+    });
+    measure!("diff (mul, compressed)", {
+        diff(&z_multiplied, &decrypted_compressed_decoded)
+    });
+
+    let ciphertext_lazy = measure!("Multiply-add ciphertexts (lazy relinearization)", {
+        (ciphertext.tensor(&ciphertext) + ciphertext.tensor(&ciphertext)).relinearize()
+    });
+    let decrypted_lazy_decoded = measure!("Decode lazy multiplied ciphertext", {
+        ckks::decode(ckks::decrypt(ciphertext_lazy, &secret_key))
+    });
+    measure!("diff (lazy mul-add)", {
+        diff(&z_multiplied.map(|c| c * 2.0), &decrypted_lazy_decoded)
+    });
+
+    // Δ に近い別々の数で rescale する法の列
+    // 従来法は scale を常に Δ とみなし、exact は各レベルの scale Δ_l を使う
+    const FACTORS: [i64; LIMIT as usize] = [1009, 1013, 1019];
+    let w = [Complex64::new(-15.0, 40.0), Complex64::new(25.5, -10.0)];
+    let z_muladd: Vec<Complex64> = z.iter().zip(w).map(|(z, w)| z * z + w).collect();
+    for (name, params) in [
+        ("conventional", Params::new(LIMIT, P, Q0, SCALE)),
+        (
+            "exact",
+            Params::new(LIMIT, P, Q0, SCALE).with_exact_scales(),
+        ),
+    ] {
+        let params = params.with_factors(FACTORS.to_vec());
+        let (public_key, secret_key, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let evaluator = Evaluator::new(params.clone(), vec![]);
+        let encrypt_at = |z: &[Complex64], level: u32| {
+            let plaintext = Encoder::at_level(&params, level).encode(z).unwrap();
+            // 法は足すときに小さい方へ揃う
+            ckks::encrypt(plaintext, public_key, evaluation_key)
+        };
+
+        let ciphertext = encrypt_at(&z, LIMIT);
+        let mut squared = measure!(format!("Multiply and rescale ({})", name), {
+            evaluator.rescale(ciphertext.clone() * ciphertext.clone())
+        });
+        if !params.exact_scales {
+            // 実際の scale Δ^2 / f_L を Δ とみなす
+            squared.scale = DELTA as f64;
+        }
+        let ciphertext_muladd = measure!(format!("Add at level {} ({})", LIMIT - 1, name), {
+            squared + encrypt_at(&w, LIMIT - 1)
+        });
+        let decoded = ckks::decode(ckks::decrypt(ciphertext_muladd, &secret_key));
+        measure!(format!("diff (mul-add, {})", name), {
+            diff(&z_muladd, &decoded)
+        });
+    }
 }
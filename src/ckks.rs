@@ -1,64 +1,145 @@
 use ciphertext::Ciphertext;
-use code::{canonical_embedding, canonical_embedding_inv, project, project_inv};
-use keys::{EvaluationKey, PublicKey, SecretKey};
+use encoder::Encoder;
+use keys::{ERROR_BOUND, EvaluationKey, GaloisKey, KeyId, KeyMismatch, PublicKey, SecretKey};
+use modulo::ConstantTime;
 use num_complex::Complex64;
 use num_integer::Integer;
 use num_traits::NumCast;
+use params::Params;
 use plaintext::Plaintext;
 use poly::{ModPoly, Poly};
 use rand::distr::uniform::SampleUniform;
+use std::ops::Neg;
+use zeroize::Zeroize;
 
+pub mod approx;
+pub mod bootstrap;
 pub mod ciphertext;
 pub mod code;
+pub mod compare;
+pub mod compress;
+pub mod encoder;
+pub mod evaluator;
 pub mod keys;
+pub mod linear;
 pub mod modulo;
+pub mod newton;
+pub mod params;
 pub mod plaintext;
 pub mod poly;
+pub mod polyeval;
+pub mod reduction;
+pub mod serialize;
+#[cfg(test)]
+mod testing;
 
-// ℂ^{N/2} -> ℤ[X]/(X^N + 1)
+// 以下は Encoder を使い捨てる簡易版 (失敗したら panic する)
+
+// ℂ^n -> ℤ[X]/(X^N + 1) (n | N/2)
 pub fn encode<T: Integer + NumCast + Copy + Default, const N: usize>(
-    z: [Complex64; N / 2],
-    scale: T,
+    z: &[Complex64],
+    scale: f64,
 ) -> Plaintext<T, N> {
-    let encoded = canonical_embedding_inv(project_inv(z));
-    // imが0のはず
-    assert!(encoded.coeffs.iter().all(|x| x.im.abs() < 1e-6));
+    Encoder::new(scale)
+        .encode(z)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
 
-    let coeffs = encoded
-        .coeffs
-        .map(|x| T::from((x.re * scale.to_f64().unwrap()).round()).unwrap());
+// ℤ[X]/(X^N + 1) -> ℂ^n (n = plaintext.slots)
+pub fn decode<T: Integer + NumCast + Copy + Default, const N: usize>(
+    plaintext: Plaintext<T, N>,
+) -> Vec<Complex64> {
+    Encoder::new(plaintext.scale)
+        .decode(&plaintext)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
 
-    Plaintext::new(Poly::new(coeffs), scale)
+// ℝ^n -> ℤ[X + X^{-1}] ⊂ ℤ[X]/(X^{2N} + 1) (n | N)
+pub fn encode_real<T: Integer + NumCast + Copy + Default, const N: usize>(
+    x: &[f64],
+    scale: f64,
+) -> Plaintext<T, N> {
+    Encoder::new(scale)
+        .encode_real(x)
+        .unwrap_or_else(|e| panic!("{}", e))
 }
 
-// ℤ[X]/(X^N + 1) -> ℂ^{N/2}
-pub fn decode<T: Integer + NumCast + Copy + Default, const N: usize>(
+// ℤ[X + X^{-1}] -> ℝ^n (n = plaintext.slots)
+pub fn decode_real<T: Integer + NumCast + Copy + Default, const N: usize>(
     plaintext: Plaintext<T, N>,
-) -> [Complex64; N / 2] {
-    let p = Poly::new(
-        plaintext
-            .m
-            .coeffs
-            .map(|x| Complex64::new(x.to_f64().unwrap() / plaintext.scale.to_f64().unwrap(), 0.0)),
-    );
+) -> Vec<f64> {
+    Encoder::new(plaintext.scale)
+        .decode_real(&plaintext)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
 
-    project(canonical_embedding(p))
+// ℝ^k -> ℤ[X]/(X^N + 1) (k <= N)
+pub fn encode_coeffs<T: Integer + NumCast + Copy + Default, const N: usize>(
+    coeffs: &[f64],
+    scale: f64,
+) -> Plaintext<T, N> {
+    Encoder::new(scale)
+        .encode_coeffs(coeffs)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+// ℤ[X]/(X^N + 1) -> ℝ^N
+pub fn decode_coeffs<T: Integer + NumCast + Copy + Default, const N: usize>(
+    plaintext: Plaintext<T, N>,
+) -> Vec<f64> {
+    Encoder::new(plaintext.scale)
+        .decode_coeffs(&plaintext)
+        .unwrap_or_else(|e| panic!("{}", e))
 }
 
-pub fn generate_keys<const N: usize>(
+pub fn generate_keys<T, const N: usize>(
     limit: u32,
-    p: i64,
-    q0: i64,
-    scale: i64,
-) -> (PublicKey<i64, N>, SecretKey<i64, N>, EvaluationKey<i64, N>) {
-    let ql = (p.pow(limit)) * q0;
-    let secret_key = SecretKey::generate(ql);
-    let public_key = PublicKey::generate(secret_key, ql);
-    let evaluation_key = EvaluationKey::generate(secret_key, scale * ql, scale);
+    p: T,
+    q0: T,
+    scale: T,
+) -> (PublicKey<T, N>, SecretKey<T, N>, EvaluationKey<T, N>)
+where
+    T: ConstantTime + Zeroize + Default + NumCast + SampleUniform + From<i64> + Neg<Output = T>,
+{
+    generate_keys_for(&Params::new(limit, p, q0, scale))
+}
+
+// 法 q_L は params の法の列で決まる
+pub fn generate_keys_for<T, const N: usize>(
+    params: &Params<T>,
+) -> (PublicKey<T, N>, SecretKey<T, N>, EvaluationKey<T, N>)
+where
+    T: ConstantTime + Zeroize + Default + NumCast + SampleUniform + From<i64> + Neg<Output = T>,
+{
+    let ql = params.modulo(params.limit);
+    let mut secret_key = SecretKey::generate_in(params.ring, ql);
+    let mut public_key = PublicKey::generate(&secret_key, ql);
+    // 評価鍵と Galois 鍵は秘密鍵の識別子を受け継ぐ
+    let id = KeyId::new(&public_key, params);
+    secret_key.id = id;
+    public_key.id = id;
+    let evaluation_key = EvaluationKey::generate(&secret_key, ql, params.scale);
 
     (public_key, secret_key, evaluation_key)
 }
 
+// X -> X^g (g ∈ elements) 用の鍵を法 P*q_L で作る
+pub fn generate_galois_keys<T, const N: usize>(
+    secret_key: &SecretKey<T, N>,
+    params: &Params<T>,
+    elements: &[usize],
+) -> Vec<GaloisKey<T, N>>
+where
+    T: ConstantTime + Zeroize + Default + SampleUniform + From<i64> + Neg<Output = T>,
+{
+    let ql = params.modulo(params.limit);
+
+    elements
+        .iter()
+        .map(|&g| GaloisKey::generate(secret_key, g, ql, params.scale))
+        .collect()
+}
+
 // ℤ[X]/(X^N + 1) -> ((ℤ/qℤ)[X]/(X^N + 1))^2
 pub fn encrypt<T, const N: usize>(
     plaintext: Plaintext<T, N>,
@@ -66,27 +147,72 @@ pub fn encrypt<T, const N: usize>(
     evaluation_key: EvaluationKey<T, N>,
 ) -> Ciphertext<T, N>
 where
-    T: Integer + Default + Copy + SampleUniform + From<i64>,
+    T: ConstantTime + Default + From<i64>,
 {
+    if let Err(error) = public_key.id.check(evaluation_key.id) {
+        panic!("{}", error);
+    }
+    assert_eq!(
+        plaintext.encoding.ring(),
+        public_key.ring,
+        "{:?} plaintexts cannot be encrypted in the {:?} ring",
+        plaintext.encoding,
+        public_key.ring
+    );
+
     let modulo = public_key.b.modulo;
     let m = ModPoly::new(plaintext.m.coeffs, modulo);
-    let v = ModPoly::new_random((-1).into()..2.into(), modulo);
-    let e0 = ModPoly::new_random((-3).into()..3.into(), modulo);
-    let e1 = ModPoly::new_random((-3).into()..3.into(), modulo);
+    let v = ModPoly::new_random_ct(-1..2, modulo);
+    let e0 = ModPoly::new_random_ct(-ERROR_BOUND..ERROR_BOUND, modulo);
+    let e1 = ModPoly::new_random_ct(-ERROR_BOUND..ERROR_BOUND, modulo);
 
-    let c0 = v * public_key.b + m + e0;
-    let c1 = v * public_key.a + e1;
+    let ring = public_key.ring;
+    let c0 = v.mul_ct(&public_key.b, ring).add_ct(&m).add_ct(&e0);
+    let c1 = v.mul_ct(&public_key.a, ring).add_ct(&e1);
 
-    Ciphertext::new(c0, c1, evaluation_key, plaintext.scale)
+    Ciphertext {
+        slots: plaintext.slots,
+        encoding: plaintext.encoding,
+        ..Ciphertext::new(c0, c1, evaluation_key, plaintext.scale)
+    }
 }
 
 // ((ℤ/qℤ)[X]/(X^N + 1))^2 -> ℤ[X]/(X^N + 1)
-pub fn decrypt<T: Integer + Copy + Default, const N: usize>(
+// 違う鍵一式の秘密鍵なら panic する (try_decrypt はエラーを返す)
+pub fn decrypt<T: ConstantTime + Zeroize + Default, const N: usize>(
     ciphertext: Ciphertext<T, N>,
-    secret_key: SecretKey<T, N>,
+    secret_key: &SecretKey<T, N>,
 ) -> Plaintext<T, N> {
-    let m = ciphertext.c0 + ciphertext.c1 * secret_key.s;
+    try_decrypt(ciphertext, secret_key).unwrap_or_else(|error| panic!("{}", error))
+}
+
+pub fn try_decrypt<T: ConstantTime + Zeroize + Default, const N: usize>(
+    ciphertext: Ciphertext<T, N>,
+    secret_key: &SecretKey<T, N>,
+) -> Result<Plaintext<T, N>, KeyMismatch> {
+    ciphertext.key_id.check(secret_key.id)?;
+
+    // Horner: c0 + s*(c1 + s*(c2 + ...))
+    let mut c = ciphertext.c.into_iter().rev();
+    let last = c.next().unwrap();
+    let s = secret_key.expose_secret();
+    let m = c.fold(last, |acc, ci| acc.mul_ct(s, secret_key.ring).add_ct(&ci));
     let m = Poly::new(m.coeffs);
 
-    Plaintext::new(m, ciphertext.scale)
+    Ok(Plaintext {
+        slots: ciphertext.slots,
+        encoding: ciphertext.encoding,
+        ..Plaintext::new(m, ciphertext.scale)
+    })
+}
+
+// -log2(max |z - z'|)
+pub fn precision_bits(expected: &[Complex64], actual: &[Complex64]) -> f64 {
+    let error = expected
+        .iter()
+        .zip(actual)
+        .map(|(z, w)| (z - w).norm())
+        .fold(0.0, f64::max);
+
+    -error.log2()
 }
@@ -0,0 +1,280 @@
+use super::{ciphertext::Ciphertext, evaluator::Evaluator, polyeval::Polynomial};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::{f64::consts::PI, fmt::Debug};
+
+// 誤差を測る格子点の数
+const GRID: usize = 4096;
+const REMEZ_ITERATIONS: usize = 30;
+
+// [a, b] 上で f(x) ≈ p(t), t = (2x - a - b) / (b - a) ∈ [-1, 1]
+// p は Chebyshev 基底
+#[derive(Debug, Clone)]
+pub struct Approximation {
+    pub polynomial: Polynomial,
+    pub interval: (f64, f64),
+    // max_{x ∈ [a, b]} |f(x) - p(t)| (格子上で測る)
+    pub error: f64,
+}
+
+impl Approximation {
+    fn new(f: impl Fn(f64) -> f64, coeffs: Vec<f64>, interval: (f64, f64)) -> Self {
+        let (a, b) = interval;
+        assert!(a < b, "empty interval [{}, {}]", a, b);
+
+        let mut approximation = Self {
+            polynomial: Polynomial::chebyshev(coeffs),
+            interval,
+            error: 0.0,
+        };
+        approximation.error = grid(a, b)
+            .map(|x| (f(x) - approximation.evaluate(x)).abs())
+            .fold(0.0, f64::max);
+        approximation
+    }
+
+    // [a, b] -> [-1, 1]
+    pub fn normalize(&self, x: f64) -> f64 {
+        let (a, b) = self.interval;
+        (2.0 * x - a - b) / (b - a)
+    }
+
+    // 平文での評価
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.polynomial.evaluate(self.normalize(x))
+    }
+}
+
+// Chebyshev 点 cos(π(j + 1/2)/(d + 1)) での補間
+pub fn chebyshev(f: impl Fn(f64) -> f64, interval: (f64, f64), degree: usize) -> Approximation {
+    let (a, b) = interval;
+    let n = degree + 1;
+    let values: Vec<f64> = (0..n)
+        .map(|j| f(denormalize((PI * (j as f64 + 0.5) / n as f64).cos(), a, b)))
+        .collect();
+
+    let coeffs = (0..n)
+        .map(|k| {
+            let sum: f64 = values
+                .iter()
+                .enumerate()
+                .map(|(j, y)| y * (PI * k as f64 * (j as f64 + 0.5) / n as f64).cos())
+                .sum();
+            if k == 0 {
+                sum / n as f64
+            } else {
+                2.0 * sum / n as f64
+            }
+        })
+        .collect();
+
+    Approximation::new(f, coeffs, interval)
+}
+
+// Remez の交換法による最良近似 (minimax)
+// 参照点 t_0 < ... < t_{d+1} で Σ c_i T_i(t_j) + (-1)^j E = f(t_j) を解き、
+// 誤差の符号が交互に変わる極値点に参照点を入れ替える
+pub fn remez(f: impl Fn(f64) -> f64, interval: (f64, f64), degree: usize) -> Approximation {
+    let (a, b) = interval;
+    let n = degree + 2;
+
+    let mut best = chebyshev(&f, interval, degree);
+    let mut points: Vec<f64> = (0..n)
+        .map(|j| -(PI * j as f64 / (n - 1) as f64).cos())
+        .collect();
+    for _ in 0..REMEZ_ITERATIONS {
+        let matrix = points
+            .iter()
+            .enumerate()
+            .map(|(j, &t)| {
+                let mut row = chebyshev_basis(t, degree);
+                row.push(if j % 2 == 0 { 1.0 } else { -1.0 });
+                row
+            })
+            .collect();
+        let values = points.iter().map(|&t| f(denormalize(t, a, b))).collect();
+        let Some(mut solution) = solve(matrix, values) else {
+            break;
+        };
+        let levelled = solution.pop().unwrap().abs();
+
+        let candidate = Approximation::new(&f, solution, interval);
+        // 最大誤差が参照点での誤差に一致すれば最良近似
+        let converged = candidate.error - levelled <= 1e-6 * candidate.error;
+        let error = |t| f(denormalize(t, a, b)) - candidate.polynomial.evaluate(t);
+        let next = alternating_extrema(error, n);
+        if candidate.error < best.error {
+            best = candidate;
+        }
+        match next {
+            Some(next) if !converged => points = next,
+            _ => break,
+        }
+    }
+
+    best
+}
+
+// [-1, 1] -> [a, b]
+fn denormalize(t: f64, a: f64, b: f64) -> f64 {
+    (t * (b - a) + a + b) / 2.0
+}
+
+fn grid(a: f64, b: f64) -> impl Iterator<Item = f64> {
+    (0..=GRID).map(move |i| a + (b - a) * i as f64 / GRID as f64)
+}
+
+// T_0(t), ..., T_d(t)
+fn chebyshev_basis(t: f64, degree: usize) -> Vec<f64> {
+    let mut basis = vec![1.0, t];
+    for i in 2..=degree {
+        basis.push(2.0 * t * basis[i - 1] - basis[i - 2]);
+    }
+    basis.truncate(degree + 1);
+    basis
+}
+
+// 符号が同じ区間ごとに |e| が最大の点を取り、n 個になるまで端から小さい方を捨てる
+fn alternating_extrema(e: impl Fn(f64) -> f64, n: usize) -> Option<Vec<f64>> {
+    let mut extrema: Vec<(f64, f64)> = Vec::new();
+    for t in grid(-1.0, 1.0) {
+        let value = e(t);
+        match extrema.last_mut() {
+            Some(last) if (last.1 >= 0.0) == (value >= 0.0) => {
+                if value.abs() > last.1.abs() {
+                    *last = (t, value);
+                }
+            }
+            _ => extrema.push((t, value)),
+        }
+    }
+    if extrema.len() < n {
+        return None;
+    }
+
+    while extrema.len() > n {
+        if extrema[0].1.abs() < extrema[extrema.len() - 1].1.abs() {
+            extrema.remove(0);
+        } else {
+            extrema.pop();
+        }
+    }
+
+    Some(extrema.into_iter().map(|(t, _)| t).collect())
+}
+
+// Ax = b (部分ピボット選択つき Gauss の消去法)
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for i in 0..n {
+        let pivot = (i..n).max_by(|&j, &k| a[j][i].abs().total_cmp(&a[k][i].abs()))?;
+        if a[pivot][i].abs() < 1e-300 {
+            return None;
+        }
+        a.swap(i, pivot);
+        b.swap(i, pivot);
+
+        let (upper, lower) = a.split_at_mut(i + 1);
+        let row = &upper[i];
+        for (j, other) in lower.iter_mut().enumerate() {
+            let factor = other[i] / row[i];
+            for (x, y) in other[i..].iter_mut().zip(&row[i..]) {
+                *x -= factor * y;
+            }
+            b[i + 1 + j] -= factor * b[i];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let sum: f64 = (i + 1..n).map(|k| a[i][k] * x[k]).sum();
+        x[i] = (b[i] - sum) / a[i][i];
+    }
+    Some(x)
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    // x ∈ [a, b] を t ∈ [-1, 1] に移してから評価する
+    // [a, b] = [-1, 1] でなければ 1 レベル余計に使う
+    pub fn evaluate_approximation(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        approximation: &Approximation,
+    ) -> Ciphertext<T, N> {
+        let (a, b) = approximation.interval;
+        let ciphertext = if (a, b) == (-1.0, 1.0) {
+            ciphertext.clone()
+        } else {
+            let scaled = self.mul_const(ciphertext.clone(), 2.0 / (b - a));
+            self.add_const(scaled, -(a + b) / (b - a))
+        };
+
+        self.evaluate_polynomial(&ciphertext, &approximation.polynomial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{self, params::Params, testing::Context};
+    use num_complex::Complex64;
+
+    fn sigmoid(x: f64) -> f64 {
+        1.0 / (1.0 + (-x).exp())
+    }
+
+    #[test]
+    fn chebyshev_interpolation() {
+        // 次数 d 以下の多項式は厳密に再現する
+        let approximation = chebyshev(|x| x * x * x - 2.0 * x, (0.0, 3.0), 3);
+        assert!(approximation.error < 1e-12);
+
+        let approximation = chebyshev(f64::exp, (-1.0, 1.0), 10);
+        assert!(approximation.error < 1e-9);
+        assert!((approximation.evaluate(0.5) - 0.5f64.exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn remez_is_better_than_chebyshev() {
+        let check = |f: fn(f64) -> f64, interval| {
+            let interpolant = chebyshev(f, interval, 7);
+            let minimax = remez(f, interval, 7);
+            assert!(minimax.error <= interpolant.error);
+        };
+        check(sigmoid, (-8.0, 8.0));
+        check(f64::tanh, (-4.0, 4.0));
+        check(f64::ln, (1.0, 16.0));
+        check(|x| 1.0 / x, (1.0, 8.0));
+    }
+
+    #[test]
+    fn remez_equioscillates() {
+        // 最良近似の誤差は d + 2 点で符号を変えながら最大値を取る
+        let (a, b) = (1.0, 8.0);
+        let approximation = remez(|x| 1.0 / x, (a, b), 5);
+        let error = |t| 1.0 / denormalize(t, a, b) - approximation.polynomial.evaluate(t);
+        let extrema = alternating_extrema(error, 7).unwrap();
+        for t in extrema {
+            assert!((error(t).abs() - approximation.error).abs() < 1e-3 * approximation.error);
+        }
+    }
+
+    #[test]
+    fn evaluate_approximation() {
+        const N: usize = 8;
+        let params = Params::<i128>::new(5, 1 << 16, 1 << 20, 1 << 16);
+        let context = Context::<N>::new(params, &[]);
+
+        let approximation = remez(sigmoid, (-8.0, 8.0), 7);
+        let x = [-6.0, -1.5, 0.5, 7.0];
+        let ciphertext = context.encrypt(&x.map(|x| Complex64::new(x, 0.0)));
+        let result = context
+            .evaluator
+            .evaluate_approximation(&ciphertext, &approximation);
+
+        // 近似誤差とは別に、暗号化による誤差だけを測る
+        let expected = x.map(|x| Complex64::new(approximation.evaluate(x), 0.0));
+        let bits = ckks::precision_bits(&expected, &context.decrypt(&result));
+        assert!(bits > 8.0);
+    }
+}
@@ -0,0 +1,334 @@
+use super::{
+    approx,
+    ciphertext::Ciphertext,
+    code::{canonical_embedding, canonical_embedding_inv, galois_element},
+    evaluator::Evaluator,
+    linear::LinearTransform,
+    plaintext::Encoding,
+    poly::{Poly, Ring},
+};
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::{f64::consts::PI, fmt::Debug};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BootstrapConfig {
+    // ModRaise 後の c0 + c1*s = m + q0*I で |I| < k を仮定する
+    pub k: f64,
+    // (1/2π) sin(2πx) の Chebyshev 近似の次数
+    pub degree: usize,
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self { k: 1.0, degree: 15 }
+    }
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N>
+where
+    [(); N / 2]:,
+{
+    // 法 q0 まで使い切った暗号文を法 q_L に戻す
+    // ModRaise -> CoeffToSlot -> EvalMod -> SlotToCoeff
+    // 係数だけを扱うので、Slots と Coefficients のどちらの暗号文でも同じ多項式に戻る
+    // 共役不変環の暗号文は扱わない
+    pub fn bootstrap(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        assert_eq!(
+            ciphertext.ring(),
+            Ring::Negacyclic,
+            "only negacyclic ciphertexts can be bootstrapped"
+        );
+        let scale = ciphertext.scale;
+
+        let raised = self.mod_raise(ciphertext);
+        let result = if ciphertext.slots < N / 2 {
+            self.bootstrap_sparse(&raised, scale)
+        } else {
+            let (real, imag) = self.coeff_to_slot(&raised);
+            let real = self.eval_mod(&real);
+            let imag = self.eval_mod(&imag);
+            self.slot_to_coeff(&real, &imag, scale)
+        };
+
+        Ciphertext {
+            encoding: ciphertext.encoding,
+            ..result
+        }
+    }
+
+    // mod q0 -> mod q_L
+    // c0 + c1*s = m + q0*I (mod q_L)
+    // scale を q0 とみなすと係数は I + m/q0 になる
+    fn mod_raise(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let q0 = self.params.q0;
+        let modulo = self.params.modulo(self.params.limit);
+        let ciphertext = ciphertext.clone().mod_down(q0);
+
+        Ciphertext {
+            c: ciphertext
+                .c
+                .into_iter()
+                .map(|c| c.with_modulo(modulo))
+                .collect(),
+            scale: q0.to_f64().unwrap(),
+            encoding: Encoding::Slots,
+            ..ciphertext
+        }
+    }
+
+    // 係数 t_j / (q0 k) をスロットに並べた暗号文を実部 (j < N/2) と虚部 (j >= N/2) で2つ作る
+    fn coeff_to_slot(&self, ciphertext: &Ciphertext<T, N>) -> (Ciphertext<T, N>, Ciphertext<T, N>) {
+        let k = self.bootstrap_config.k;
+        let (scale, rescales) = self.coeff_to_slot_scale();
+
+        let transform = |offset: usize| {
+            let matrix = matrix_of::<N>(|w| {
+                let c = canonical_embedding_inv(w).coeffs;
+                std::array::from_fn(|i| c[offset + i.min(N - 1 - i)] / k)
+            });
+            let transform = LinearTransform::from_embedding(&matrix, scale);
+            let mut result = self.linear_transform(ciphertext, &transform);
+            for _ in 0..rescales {
+                result = self.rescale(result);
+            }
+            result
+        };
+
+        (transform(0), transform(N / 2))
+    }
+
+    // 行列を scale Δ_{L-r} f_L ... f_{L-r+1} / q0 で符号化して r 回 rescale すると scale が Δ_{L-r} になる
+    // (f_L ... f_{L-r+1} >= q0 となる最小の r)
+    fn coeff_to_slot_scale(&self) -> (f64, u32) {
+        let limit = self.params.limit;
+        let q0 = self.params.q0.to_f64().unwrap();
+
+        let mut rescales = 0;
+        let mut product = 1.0;
+        while product < q0 {
+            product *= self.params.factor(limit - rescales).to_f64().unwrap();
+            rescales += 1;
+        }
+        let scale = self.params.level_scale(limit - rescales) * product / q0;
+
+        (scale, rescales)
+    }
+
+    // (1/2π) sin(2π k v) ≈ (k v mod 1)
+    fn eval_mod(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let k = self.bootstrap_config.k;
+        let approximation = approx::chebyshev(
+            |v| (2.0 * PI * k * v).sin() / (2.0 * PI),
+            (-1.0, 1.0),
+            self.bootstrap_config.degree,
+        );
+
+        self.evaluate_approximation(ciphertext, &approximation)
+    }
+
+    // スロットの値 (≈ m_j/q0) を係数 m_j/Δ に戻す
+    fn slot_to_coeff(
+        &self,
+        real: &Ciphertext<T, N>,
+        imag: &Ciphertext<T, N>,
+        scale: f64,
+    ) -> Ciphertext<T, N> {
+        let factor = self.params.q0.to_f64().unwrap() / scale;
+
+        let transform = |offset: usize| {
+            let matrix = matrix_of::<N>(|w| {
+                let mut c = [Complex64::default(); N];
+                for j in 0..N / 2 {
+                    // Re(w_j) = (w_j + w_{N-1-j}) / 2
+                    c[offset + j] = (w[j] + w[N - 1 - j]) / 2.0 * factor;
+                }
+                canonical_embedding(Poly::new(c))
+            });
+            LinearTransform::from_embedding(&matrix, self.params.p.to_f64().unwrap())
+        };
+
+        let real = self.linear_transform(real, &transform(0));
+        let imag = self.linear_transform(imag, &transform(N / 2));
+
+        self.rescale(real + imag)
+    }
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N>
+where
+    [(); N / 2]:,
+{
+    // n < N/2 スロットの場合
+    // 部分環 ℤ[X^{N/2n}] の 2n 個の係数は1つの暗号文のスロットに収まるので EvalMod は1回で済む
+    fn bootstrap_sparse(&self, raised: &Ciphertext<T, N>, scale: f64) -> Ciphertext<T, N> {
+        let slots = raised.slots;
+        let gap = N / (2 * slots);
+
+        // Σ_{j < N/2n} τ_{5^{nj}} で q0*I の部分環の外の係数を消す (部分環の係数は N/2n 倍になる)
+        let traced = (0..gap.trailing_zeros())
+            .map(|j| galois_element::<N>(slots << j))
+            .fold(raised.clone(), |acc, g| {
+                let rotated = self.apply_galois(&acc, g);
+                acc + rotated
+            });
+
+        let packed = self.coeff_to_slot_sparse(&traced);
+        let packed = self.eval_mod(&packed);
+        let result = self.slot_to_coeff_sparse(&packed, slots, scale);
+
+        Ciphertext { slots, ..result }
+    }
+
+    // 係数 t_{j gap} / (q0 k gap) (j < 2n) をスロットに周期 2n で並べる
+    fn coeff_to_slot_sparse(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let k = self.bootstrap_config.k;
+        let slots = ciphertext.slots;
+        let gap = N / (2 * slots);
+        let (scale, rescales) = self.coeff_to_slot_scale();
+
+        let matrix = matrix_of::<N>(|w| {
+            let c = canonical_embedding_inv(w).coeffs;
+            std::array::from_fn(|i| c[i.min(N - 1 - i) % (2 * slots) * gap] / (k * gap as f64))
+        });
+        let transform = LinearTransform::from_embedding(&matrix, scale);
+        let mut result = self.linear_transform(ciphertext, &transform);
+        for _ in 0..rescales {
+            result = self.rescale(result);
+        }
+        result
+    }
+
+    // スロット j < 2n の値 (≈ m_{j gap}/q0) を係数 m_{j gap}/Δ に戻す
+    fn slot_to_coeff_sparse(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        slots: usize,
+        scale: f64,
+    ) -> Ciphertext<T, N> {
+        let factor = self.params.q0.to_f64().unwrap() / scale;
+        let gap = N / (2 * slots);
+
+        let matrix = matrix_of::<N>(|w| {
+            let mut c = [Complex64::default(); N];
+            for j in 0..2 * slots {
+                c[j * gap] = (w[j] + w[N - 1 - j]) / 2.0 * factor;
+            }
+            canonical_embedding(Poly::new(c))
+        });
+        let transform = LinearTransform::from_embedding(&matrix, self.params.p.to_f64().unwrap());
+
+        self.rescale(self.linear_transform(ciphertext, &transform))
+    }
+}
+
+// f: ℂ^N -> ℂ^N (線形) の行列 M[i][j] = f(e_j)[i]
+fn matrix_of<const N: usize>(f: impl Fn([Complex64; N]) -> [Complex64; N]) -> Vec<[Complex64; N]> {
+    let columns: Vec<[Complex64; N]> = (0..N)
+        .map(|j| {
+            let mut e = [Complex64::default(); N];
+            e[j] = Complex64::new(1.0, 0.0);
+            f(e)
+        })
+        .collect();
+
+    (0..N)
+        .map(|i| std::array::from_fn(|j| columns[j][i]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        self,
+        params::Params,
+        testing::{Context, Z},
+    };
+
+    const N: usize = 8;
+
+    fn params() -> Params<i128> {
+        Params::new(8, 1 << 11, 1 << 14, 1 << 20)
+    }
+
+    fn context(params: Params<i128>) -> Context<N> {
+        let elements: Vec<usize> = (3..2 * N).step_by(2).collect();
+        Context::new(params, &elements)
+    }
+
+    // p で割る法の列と、rescale ごとに scale がずれる法の列
+    fn contexts() -> [Context<N>; 2] {
+        let factors = vec![2039, 2053, 2029, 2063, 2027, 2069, 2017, 2081];
+        [context(params()), context(params().with_factors(factors))]
+    }
+
+    // 1 回ごとの精度はばらつくので、TRIALS 回の暗号化の中央値で比べる
+    const TRIALS: usize = 9;
+
+    fn median(mut bits: Vec<f64>) -> f64 {
+        bits.sort_by(f64::total_cmp);
+        bits[bits.len() / 2]
+    }
+
+    #[test]
+    fn bootstrap() {
+        for context in contexts() {
+            check_bootstrap(context);
+        }
+    }
+
+    fn check_bootstrap(context: Context<N>) {
+        let params = &context.evaluator.params;
+        let evaluator = &context.evaluator;
+
+        let (bits, squared_bits): (Vec<_>, Vec<_>) = (0..TRIALS)
+            .map(|_| {
+                let ciphertext = context.encrypt(&Z).mod_down(params.q0);
+                assert_eq!(evaluator.level(&ciphertext), 0);
+
+                let bootstrapped = evaluator.bootstrap(&ciphertext);
+                assert_eq!(evaluator.level(&bootstrapped), 1);
+                let bits = ckks::precision_bits(&Z, &context.decrypt(&bootstrapped));
+
+                // 残った1レベルで掛け算できる
+                let squared = evaluator.rescale(bootstrapped.clone() * bootstrapped);
+                let squared_bits =
+                    ckks::precision_bits(&Z.map(|z| z * z), &context.decrypt(&squared));
+                (bits, squared_bits)
+            })
+            .unzip();
+        // 中央値は 1000 回で最小 3.57 bits、中央値 4.14 bits (2 乗は最小 2.95 bits、中央値 3.59 bits)
+        // 1 回ごとでは最小 3.07 bits、中央値 4.15 bits で、EvalMod を 5 次の近似にすると中央値 2.5 bits まで落ちる
+        assert!(median(bits) > 3.4);
+        assert!(median(squared_bits) > 2.8);
+    }
+
+    #[test]
+    fn bootstrap_sparse() {
+        for context in contexts() {
+            check_bootstrap_sparse(context);
+        }
+    }
+
+    fn check_bootstrap_sparse(context: Context<N>) {
+        let evaluator = &context.evaluator;
+
+        let z = [Complex64::new(0.5, -0.25), Complex64::new(-0.75, 0.1)];
+        let bits = (0..TRIALS)
+            .map(|_| {
+                let ciphertext = context.encrypt(&z).mod_down(evaluator.params.q0);
+
+                let bootstrapped = evaluator.bootstrap(&ciphertext);
+                assert_eq!(bootstrapped.slots, 2);
+                assert_eq!(evaluator.level(&bootstrapped), 1);
+                let decrypted = context.decrypt(&bootstrapped);
+                assert_eq!(decrypted.len(), 2);
+                ckks::precision_bits(&z, &decrypted)
+            })
+            .collect();
+        // 中央値は 1000 回で最小 3.98 bits、中央値 4.84 bits
+        assert!(median(bits) > 3.8);
+    }
+}
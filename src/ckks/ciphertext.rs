@@ -1,14 +1,35 @@
-use super::{keys::EvaluationKey, poly::ModPoly};
+use super::{
+    keys::{EvaluationKey, GaloisKey, KeyId, KeyMismatch},
+    modulo::round_div,
+    params::Params,
+    plaintext::{Encoding, Plaintext, scales_match},
+    poly::{ModPoly, Ring},
+};
 use num_integer::Integer;
+use num_traits::NumCast;
 use std::fmt::Debug;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Neg, Sub};
 
-#[derive(Debug, Clone, Copy)]
+// Deserialize は key_id と評価鍵の識別子を確かめる (serialize.rs)
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(bound(
+        serialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Serialize"
+    ))
+)]
 pub struct Ciphertext<T: Integer, const N: usize> {
-    pub c0: ModPoly<T, N>,
-    pub c1: ModPoly<T, N>,
+    // [c0, c1, c2, ...]
+    // Dec(c) = c0 + c1*s + c2*s^2 + ...
+    pub c: Vec<ModPoly<T, N>>,
     pub evaluation_key: EvaluationKey<T, N>,
-    pub scale: T,
+    // 暗号化した鍵一式
+    pub key_id: KeyId,
+    pub scale: f64,
+    // 使っているスロット数 (Plaintext::slots と同じ)
+    pub slots: usize,
+    pub encoding: Encoding,
 }
 
 impl<T: Integer, const N: usize> Ciphertext<T, N> {
@@ -16,13 +37,172 @@ impl<T: Integer, const N: usize> Ciphertext<T, N> {
         c0: ModPoly<T, N>,
         c1: ModPoly<T, N>,
         evaluation_key: EvaluationKey<T, N>,
-        scale: T,
+        scale: f64,
     ) -> Self {
+        Self::from_components(vec![c0, c1], evaluation_key, scale)
+    }
+
+    pub fn from_components(
+        c: Vec<ModPoly<T, N>>,
+        evaluation_key: EvaluationKey<T, N>,
+        scale: f64,
+    ) -> Self {
+        assert!(c.len() >= 2);
+
         Self {
-            c0,
-            c1,
+            c,
+            key_id: evaluation_key.id,
             evaluation_key,
             scale,
+            slots: N / 2,
+            encoding: Encoding::Slots,
+        }
+    }
+
+    // s の最高次数
+    pub fn degree(&self) -> usize {
+        self.c.len() - 1
+    }
+
+    // 鍵一式の環
+    pub fn ring(&self) -> Ring {
+        self.evaluation_key.ring
+    }
+}
+
+impl<T: Integer + Default + Copy + Debug, const N: usize> Ciphertext<T, N> {
+    pub fn modulo(&self) -> T {
+        self.c[0].modulo
+    }
+
+    // q' | q のとき mod q -> mod q'
+    pub(crate) fn mod_down(self, modulo: T) -> Self {
+        if self.modulo() == modulo {
+            return self;
+        }
+        assert!(self.modulo() % modulo == T::zero());
+
+        Self {
+            c: self.c.into_iter().map(|c| c.reduce(modulo)).collect(),
+            ..self
+        }
+    }
+
+    // mod q_l -> mod q_level (scale はそのまま)
+    pub fn mod_switch_to(self, params: &Params<T>, level: u32) -> Self {
+        let current = params.level(self.modulo());
+        assert!(
+            level <= current,
+            "cannot switch from level {} up to level {}",
+            current,
+            level
+        );
+
+        self.mod_down(params.modulo(level))
+    }
+
+    // mod q_l -> mod q_{l-1} (rescale と違って f_l で割らない)
+    pub fn mod_switch_to_next(self, params: &Params<T>) -> Self {
+        let current = params.level(self.modulo());
+        assert!(current > 0, "cannot switch below level 0");
+
+        self.mod_switch_to(params, current - 1)
+    }
+
+    // 法の小さい方に揃える
+    fn align(self, rhs: Self) -> (Self, Self) {
+        let modulo = self.modulo().min(rhs.modulo());
+
+        (self.mod_down(modulo), rhs.mod_down(modulo))
+    }
+
+    // (Σ a_i s^i)(Σ b_j s^j) = Σ_k (Σ_{i+j=k} a_i b_j) s^k
+    // 再線形化はしない
+    pub fn tensor(&self, rhs: &Self) -> Self {
+        assert_same_key(self.key_id, rhs.key_id);
+
+        let (lhs, rhs) = self.clone().align(rhs.clone());
+        let modulo = lhs.modulo();
+        let ring = lhs.ring();
+        let mut c = vec![ModPoly::new([T::zero(); N], modulo); lhs.c.len() + rhs.c.len() - 1];
+        for (i, a) in lhs.c.iter().enumerate() {
+            for (j, b) in rhs.c.iter().enumerate() {
+                c[i + j] = c[i + j] + a.mul_in(*b, ring);
+            }
+        }
+
+        Self {
+            c,
+            evaluation_key: lhs.evaluation_key,
+            key_id: lhs.key_id,
+            scale: lhs.scale * rhs.scale,
+            slots: lhs.slots.max(rhs.slots),
+            encoding: lhs.encoding.combine(rhs.encoding),
+        }
+    }
+
+    // 違う鍵一式の暗号文どうしならエラー (Mul は panic する)
+    pub fn try_mul(self, rhs: Self) -> Result<Self, KeyMismatch> {
+        self.key_id.check(rhs.key_id)?;
+        Ok(self.tensor(&rhs).relinearize())
+    }
+
+    // (c0, c1, c2) -> (c0 + c2*b/P, c1 + c2*a/P)
+    pub fn relinearize(self) -> Self {
+        assert!(
+            self.c.len() <= 3,
+            "only ciphertexts of degree <= 2 can be relinearized"
+        );
+        if self.c.len() == 2 {
+            return self;
+        }
+        assert_same_key(self.key_id, self.evaluation_key.id);
+
+        let (d0, d1) = self.evaluation_key.switch(self.c[2]);
+
+        Self {
+            c: vec![self.c[0] + d0, self.c[1] + d1],
+            ..self
+        }
+    }
+
+    // (c0, c1, ...) mod q -> (c0/p, c1/p, ...) mod q/p
+    // scale も p で割る
+    pub fn rescale(self, p: T) -> Self
+    where
+        T: NumCast,
+    {
+        let modulo = self.modulo() / p;
+
+        Self {
+            c: self
+                .c
+                .into_iter()
+                .map(|c| ModPoly::new(c.coeffs.map(|x| round_div(x, p)), modulo))
+                .collect(),
+            scale: self.scale / p.to_f64().unwrap(),
+            ..self
+        }
+    }
+
+    // τ_g(c0) + τ_g(c1)*s' -> s' = τ_g(s) から s へ鍵を切り替える
+    pub fn automorphism(self, galois_key: &GaloisKey<T, N>) -> Self {
+        assert_eq!(
+            self.degree(),
+            1,
+            "relinearize before applying automorphisms"
+        );
+
+        assert_same_key(self.key_id, galois_key.key.id);
+
+        let ring = self.ring();
+        let c0 = self.c[0].automorphism_in(galois_key.g, ring);
+        let c1 = self.c[1].automorphism_in(galois_key.g, ring);
+        let (d0, d1) = galois_key.key.switch(c1);
+
+        Self {
+            c: vec![c0 + d0, d1],
+            ..self
         }
     }
 }
@@ -31,38 +211,347 @@ impl<T: Integer + Default + Copy + Debug, const N: usize> Add for Ciphertext<T,
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self {
-        assert_eq!(self.scale, rhs.scale);
+        assert_scales_match(self.scale, rhs.scale);
+        assert_same_key(self.key_id, rhs.key_id);
+
+        let (lhs, rhs) = self.align(rhs);
+        let (mut c, rest) = if lhs.c.len() >= rhs.c.len() {
+            (lhs.c, rhs.c)
+        } else {
+            (rhs.c, lhs.c)
+        };
+        for (ci, ri) in c.iter_mut().zip(rest) {
+            *ci = *ci + ri;
+        }
+
+        Self {
+            c,
+            evaluation_key: lhs.evaluation_key,
+            key_id: lhs.key_id,
+            scale: lhs.scale,
+            slots: lhs.slots.max(rhs.slots),
+            encoding: lhs.encoding.combine(rhs.encoding),
+        }
+    }
+}
+
+impl<T: Integer + Default + Copy + Debug, const N: usize> Neg for Ciphertext<T, N> {
+    type Output = Self;
 
+    fn neg(self) -> Self {
         Self {
-            c0: self.c0 + rhs.c0,
-            c1: self.c1 + rhs.c1,
+            c: self
+                .c
+                .into_iter()
+                .map(|c| ModPoly::new([T::zero(); N], c.modulo) - c)
+                .collect(),
             ..self
         }
     }
 }
 
+impl<T: Integer + Default + Copy + Debug, const N: usize> Sub for Ciphertext<T, N> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
 impl<T: Integer + Default + Copy + Debug, const N: usize> Mul for Ciphertext<T, N> {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self {
-        // TODO: とりあえず
-        assert_eq!(self.scale, rhs.scale);
+        self.tensor(&rhs).relinearize()
+    }
+}
+
+impl<T: Integer + Default + Copy + Debug, const N: usize> Add<Plaintext<T, N>>
+    for Ciphertext<T, N>
+{
+    type Output = Self;
+
+    fn add(mut self, rhs: Plaintext<T, N>) -> Self {
+        assert_scales_match(self.scale, rhs.scale);
+
+        let modulo = self.modulo();
+        self.c[0] = self.c[0] + ModPoly::new(rhs.m.coeffs, modulo).reduce(modulo);
+        self.slots = self.slots.max(rhs.slots);
+        self.encoding = self.encoding.combine(rhs.encoding);
+        self
+    }
+}
 
-        let c0 = self.c0 * rhs.c0;
-        let c1 = self.c0 * rhs.c1 + self.c1 * rhs.c0;
-        let c2 = self.c1 * rhs.c1;
+impl<T: Integer + Default + Copy + Debug, const N: usize> Mul<Plaintext<T, N>>
+    for Ciphertext<T, N>
+{
+    type Output = Self;
 
-        // calculate with evaluation key's modulo
-        let EvaluationKey { b, a } = self.evaluation_key;
-        let new_modulo = b.modulo;
-        let d0 = c0.with_modulo(new_modulo) + (c2.with_modulo(new_modulo) * b / self.scale);
-        let d1 = c1.with_modulo(new_modulo) + (c2.with_modulo(new_modulo) * a / self.scale);
+    fn mul(self, rhs: Plaintext<T, N>) -> Self {
+        let modulo = self.modulo();
+        let ring = self.ring();
+        let m = ModPoly::new(rhs.m.coeffs, modulo).reduce(modulo);
 
         Self {
-            c0: d0,
-            c1: d1,
-            evaluation_key: self.evaluation_key,
-            scale: self.scale * self.scale,
+            c: self.c.into_iter().map(|c| c.mul_in(m, ring)).collect(),
+            scale: self.scale * rhs.scale,
+            slots: self.slots.max(rhs.slots),
+            encoding: self.encoding.combine(rhs.encoding),
+            ..self
         }
     }
 }
+
+// scale のずれは Evaluator::add で直す
+fn assert_scales_match(a: f64, b: f64) {
+    assert!(
+        scales_match(a, b),
+        "scales {} and {} differ (use Evaluator::add to adjust)",
+        a,
+        b
+    );
+}
+
+fn assert_same_key(a: KeyId, b: KeyId) {
+    if let Err(error) = a.check(b) {
+        panic!("{}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn lazy_relinearization() {
+        use crate::ckks;
+        use num_complex::Complex64;
+
+        const N: usize = 4;
+        let z1 = [Complex64::new(1.5, 0.0), Complex64::new(-2.0, 1.0)];
+        let z2 = [Complex64::new(3.0, -1.0), Complex64::new(0.5, 0.5)];
+
+        let (public_key, secret_key, evaluation_key) =
+            ckks::generate_keys::<i64, N>(3, 1000, 1000, 1000);
+        let encrypt = |z| {
+            ckks::encrypt(
+                ckks::encode::<i64, N>(z, 10000.0),
+                public_key,
+                evaluation_key,
+            )
+        };
+        let c1 = encrypt(&z1);
+        let c2 = encrypt(&z2);
+
+        // z1*z2 + z2*z2 を再線形化1回で計算する
+        let lazy = c1.tensor(&c2) + c2.tensor(&c2);
+        assert_eq!(lazy.degree(), 2);
+
+        let expected: Vec<_> = (0..N / 2).map(|i| z1[i] * z2[i] + z2[i] * z2[i]).collect();
+        let unrelinearized = ckks::decode(ckks::decrypt(lazy.clone(), &secret_key));
+        let relinearized = lazy.relinearize();
+        assert_eq!(relinearized.degree(), 1);
+        let relinearized = ckks::decode(ckks::decrypt(relinearized, &secret_key));
+
+        for i in 0..N / 2 {
+            assert!((unrelinearized[i] - expected[i]).norm() < 0.1);
+            assert!((relinearized[i] - expected[i]).norm() < 0.1);
+        }
+    }
+
+    #[test]
+    fn mod_switch() {
+        use crate::ckks;
+        use num_complex::Complex64;
+
+        const N: usize = 4;
+        let z = [Complex64::new(1.5, 0.0), Complex64::new(-2.0, 1.0)];
+        let params = super::Params::new(3, 1000, 1000, 1000);
+
+        let (public_key, secret_key, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let ciphertext = ckks::encrypt(
+            ckks::encode::<i64, N>(&z, 10000.0),
+            public_key,
+            evaluation_key,
+        );
+
+        // scale はそのままで modulo: q_3 -> q_2 -> q_1
+        let next = ciphertext.mod_switch_to_next(&params);
+        assert_eq!(next.modulo(), 1000i64.pow(3));
+        let switched = next.mod_switch_to(&params, 1);
+        assert_eq!(switched.modulo(), 1000i64.pow(2));
+        assert_eq!(switched.scale, 10000.0);
+
+        let decoded = ckks::decode(ckks::decrypt(switched, &secret_key));
+        for i in 0..N / 2 {
+            assert!((decoded[i] - z[i]).norm() < 0.1);
+        }
+    }
+
+    #[test]
+    fn plaintext_mod_switch() {
+        use crate::ckks::{self, plaintext::Plaintext, poly::Poly};
+
+        const N: usize = 4;
+        let params = super::Params::new(3, 1000, 1000, 1000);
+
+        // q_1 = 10^6 -> q_0 = 1000
+        let plaintext = Plaintext::new(Poly::<i64, N>::new([400_001, 2_003, 0, -7]), 1.0);
+        let next = plaintext
+            .mod_switch_to(&params, 1)
+            .mod_switch_to_next(&params);
+        assert_eq!(next.m.coeffs, [1, 3, 0, -7]);
+        assert_eq!(next.scale, 1.0);
+
+        // 同じレベルに下げた暗号文とそのまま足せる
+        let (public_key, secret_key, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let x = [0.5, -1.25, 2.0, 0.0];
+        let y = [1.0, 0.25, -0.5, 3.0];
+        let ciphertext = ckks::encrypt(
+            ckks::encode_coeffs::<i64, N>(&x, 10000.0),
+            public_key,
+            evaluation_key,
+        );
+        let plaintext = ckks::encode_coeffs::<i64, N>(&y, 10000.0).mod_switch_to(&params, 3);
+        let sum = ciphertext.mod_switch_to_next(&params) + plaintext.mod_switch_to_next(&params);
+        assert_eq!(sum.modulo(), 1000i64.pow(3));
+
+        let decoded = ckks::decode_coeffs(ckks::decrypt(sum, &secret_key));
+        for i in 0..N {
+            assert!((decoded[i] - x[i] - y[i]).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot switch below level 0")]
+    fn plaintext_mod_switch_below_zero() {
+        use crate::ckks::{plaintext::Plaintext, poly::Poly};
+
+        let params = super::Params::new(3, 1000, 1000, 1000);
+        let plaintext = Plaintext::new(Poly::<i64, 4>::new([1, 0, 0, 0]), 1.0);
+        let _ = plaintext
+            .mod_switch_to(&params, 0)
+            .mod_switch_to_next(&params);
+    }
+
+    #[test]
+    #[should_panic(expected = "plaintext has no modulus")]
+    fn plaintext_mod_switch_without_modulus() {
+        use crate::ckks::{plaintext::Plaintext, poly::Poly};
+
+        let params = super::Params::new(3, 1000, 1000, 1000);
+        let plaintext = Plaintext::new(Poly::<i64, 4>::new([1, 0, 0, 0]), 1.0);
+        let _ = plaintext.mod_switch_to_next(&params);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot switch")]
+    fn mod_switch_up() {
+        use crate::ckks;
+
+        const N: usize = 4;
+        let params = super::Params::new(3, 1000, 1000, 1000);
+        let (public_key, _, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let plaintext = ckks::encode_coeffs::<i64, N>(&[1.0], 100.0);
+        let ciphertext = ckks::encrypt(plaintext, public_key, evaluation_key);
+
+        let _ = ciphertext
+            .mod_switch_to(&params, 1)
+            .mod_switch_to(&params, 2);
+    }
+
+    #[test]
+    fn rescale() {
+        use crate::ckks;
+        use num_complex::Complex64;
+
+        const N: usize = 4;
+        let z = [Complex64::new(1.5, 0.0), Complex64::new(-2.0, 1.0)];
+
+        let (public_key, secret_key, evaluation_key) =
+            ckks::generate_keys::<i64, N>(3, 1000, 1000, 1000);
+        let ciphertext = ckks::encrypt(
+            ckks::encode::<i64, N>(&z, 10000.0),
+            public_key,
+            evaluation_key,
+        );
+
+        // scale: 10000^2 -> 100000, modulo: q_3 -> q_2
+        let squared = (ciphertext.clone() * ciphertext).rescale(1000);
+        assert_eq!(squared.scale, 100000.0);
+        assert_eq!(squared.modulo(), 1000i64.pow(3));
+
+        let decoded = ckks::decode(ckks::decrypt(squared, &secret_key));
+        for i in 0..N / 2 {
+            assert!((decoded[i] - z[i] * z[i]).norm() < 0.1);
+        }
+    }
+
+    #[test]
+    fn coefficient_encoding() {
+        use crate::ckks;
+
+        const N: usize = 4;
+        let a = [1.5, -0.5, 0.25, 1.0];
+        let b = [0.5, 1.0, -1.0];
+
+        let (public_key, secret_key, evaluation_key) =
+            ckks::generate_keys::<i64, N>(3, 1000, 1000, 1000);
+        let encrypt = |x: &[f64]| {
+            ckks::encrypt(
+                ckks::encode_coeffs::<i64, N>(x, 10000.0),
+                public_key,
+                evaluation_key,
+            )
+        };
+
+        // ℝ[X]/(X^N + 1) での積
+        let product = (encrypt(&a) * encrypt(&b)).rescale(1000);
+        let decoded = ckks::decode_coeffs(ckks::decrypt(product, &secret_key));
+
+        let mut expected = [0.0; N];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                if i + j < N {
+                    expected[i + j] += x * y;
+                } else {
+                    expected[i + j - N] -= x * y;
+                }
+            }
+        }
+        for (x, y) in decoded.iter().zip(expected) {
+            assert!((x - y).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not belong to key set")]
+    fn relinearize_with_other_key() {
+        use crate::ckks;
+
+        const N: usize = 4;
+        let params = super::Params::new(3, 1000, 1000, 1000);
+        let (public_key, _, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let (_, _, other_evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let plaintext = ckks::encode_coeffs::<i64, N>(&[1.0], 100.0);
+        let ciphertext = ckks::encrypt(plaintext, public_key, evaluation_key);
+
+        let tensor = super::Ciphertext {
+            evaluation_key: other_evaluation_key,
+            ..ciphertext.tensor(&ciphertext)
+        };
+        let _ = tensor.relinearize();
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot combine")]
+    fn mismatched_encodings() {
+        use crate::ckks;
+        use num_complex::Complex64;
+
+        const N: usize = 4;
+        let (public_key, _, evaluation_key) = ckks::generate_keys::<i64, N>(3, 1000, 1000, 1000);
+        let slots = ckks::encode::<i64, N>(&[Complex64::new(1.0, 0.0); 2], 1000.0);
+        let coeffs = ckks::encode_coeffs::<i64, N>(&[1.0, 2.0], 1000.0);
+
+        let _ = ckks::encrypt(slots, public_key, evaluation_key) + coeffs;
+    }
+}
@@ -2,15 +2,56 @@ use super::poly::Poly;
 use num_complex::Complex64;
 use std::f64::consts::PI;
 
+// i 番目の成分に対応する ξ の指数
+// 前半は 5 の軌道 [ξ, ξ^5, ξ^{25}, ...]、後半はその共役を逆順に並べる
+// [ξ^{5^0}, ..., ξ^{5^{N/2-1}}, ξ^{-5^{N/2-1}}, ..., ξ^{-5^0}]
+// X -> X^{5^k} が前半の巡回シフトになる
+pub fn root_exponent<const N: usize>(i: usize) -> usize {
+    if i < N / 2 {
+        galois_element::<N>(i)
+    } else {
+        2 * N - galois_element::<N>(N - 1 - i)
+    }
+}
+
+// ξ^e が何番目の成分か (e は奇数)
+pub fn root_index<const N: usize>(e: usize) -> usize {
+    let e = e % (2 * N);
+    (0..N)
+        .find(|&i| root_exponent::<N>(i) == e)
+        .unwrap_or_else(|| panic!("ξ^{} is not a primitive 2N-th root", e))
+}
+
+// σ(P(X^g))[i] = P(ξ^{e_i g}) = σ(P)[perm[i]]
+pub fn galois_permutation<const N: usize>(g: usize) -> [usize; N] {
+    std::array::from_fn(|i| root_index::<N>(root_exponent::<N>(i) * g % (2 * N)))
+}
+
+// 5^k mod 2N
+pub fn galois_element<const N: usize>(k: usize) -> usize {
+    (0..k).fold(1, |g, _| g * 5 % (2 * N))
+}
+
+// 5^k mod 4N
+// 共役不変環 ℤ[X + X^{-1}] ⊂ ℤ[X]/(X^{2N} + 1) の N 個の実数スロットを k だけ巡回シフトする
+pub fn real_galois_element<const N: usize>(k: usize) -> usize {
+    (0..k).fold(1, |g, _| g * 5 % (4 * N))
+}
+
+// ξ -> ξ^{-1} (複素共役)
+pub fn conjugation_element<const N: usize>() -> usize {
+    2 * N - 1
+}
+
 // σ: ℂ[X] -> ℂ^N
-// σ(P) = [P(ξ), P(ξ^3), ..., P(ξ^{2N-1})]
+// σ(P) = [P(ξ^{e_0}), P(ξ^{e_1}), ..., P(ξ^{e_{N-1}})] (e_i = root_exponent(i))
 pub fn canonical_embedding<const N: usize>(p: Poly<Complex64, N>) -> [Complex64; N] {
     // ξ = e^(2πi/(2*N))
     let xi = PI / N as f64;
 
     let mut result = [Complex64::default(); N];
     for (i, res) in result.iter_mut().enumerate() {
-        let theta = xi * (2.0 * (i as f64) + 1.0);
+        let theta = xi * root_exponent::<N>(i) as f64;
         let x = Complex64::from_polar(1.0, theta);
         *res = p.evaluate(x);
     }
@@ -29,7 +70,7 @@ pub fn canonical_embedding_inv<const N: usize>(z: [Complex64; N]) -> Poly<Comple
                 .iter()
                 .enumerate()
                 .map(|(j, &zj)| {
-                    let theta = (i as f64) * xi * (2.0 * (j as f64) + 1.0);
+                    let theta = (i as f64) * xi * root_exponent::<N>(j) as f64;
                     zj * Complex64::from_polar(1.0, theta).conj()
                 })
                 .sum();
@@ -61,3 +102,117 @@ pub fn project_inv<const N: usize>(first: [Complex64; N / 2]) -> [Complex64; N]
 
     [first, second].concat().try_into().unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::plaintext::Encoding;
+
+    const N: usize = 16;
+
+    #[test]
+    fn root_exponents_cover_all_odd_residues() {
+        let mut exponents: Vec<usize> = (0..N).map(root_exponent::<N>).collect();
+        exponents.sort();
+        assert_eq!(exponents, (1..2 * N).step_by(2).collect::<Vec<_>>());
+        for i in 0..N {
+            assert_eq!(root_index::<N>(root_exponent::<N>(i)), i);
+        }
+    }
+
+    #[test]
+    fn rotation_is_cyclic_shift() {
+        use crate::ckks::{decode, encode, plaintext::Plaintext, poly::ModPoly};
+
+        let z: [Complex64; N / 2] =
+            std::array::from_fn(|i| Complex64::new(i as f64, 1.0 - 0.5 * i as f64));
+        let plaintext = encode::<i64, N>(&z, (1 << 20) as f64);
+
+        for k in 0..N / 2 {
+            // encode -> X -> X^{5^k} -> decode
+            let m = ModPoly::new(plaintext.m.coeffs, 1 << 40).automorphism(galois_element::<N>(k));
+            let rotated = decode(Plaintext::new(Poly::new(m.coeffs), plaintext.scale));
+
+            let mut expected = z;
+            expected.rotate_left(k);
+            for (a, b) in rotated.iter().zip(&expected) {
+                assert!((a - b).norm() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn conjugation() {
+        let z: [Complex64; N / 2] = std::array::from_fn(|i| Complex64::new(i as f64, 1.0));
+        let permutation = galois_permutation::<N>(conjugation_element::<N>());
+        let w = project_inv::<N>(z);
+        let conjugated = project::<N>(std::array::from_fn(|i| w[permutation[i]]));
+
+        assert_eq!(conjugated, z.map(|z| z.conj()));
+    }
+
+    #[test]
+    fn sparse_packing() {
+        use crate::ckks::{decode, encode};
+
+        let z = [Complex64::new(0.5, -0.25), Complex64::new(-0.75, 0.1)];
+        let plaintext = encode::<i64, N>(&z, (1 << 20) as f64);
+        assert_eq!(plaintext.slots, 2);
+
+        // m ∈ ℤ[X^{N/4}]
+        for (i, &c) in plaintext.m.coeffs.iter().enumerate() {
+            assert!(i % (N / 4) == 0 || c == 0);
+        }
+
+        let decoded = decode(plaintext);
+        assert_eq!(decoded.len(), 2);
+        for (a, b) in decoded.iter().zip(&z) {
+            assert!((a - b).norm() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn real_slots_are_conjugate_invariant() {
+        use crate::ckks::{decode_real, encode_real, plaintext::Plaintext, poly::ModPoly};
+
+        // N 個の実数を基底 1, X^j + X^{-j} で符号化する
+        let x: Vec<f64> = (0..N).map(|i| 0.25 * i as f64 - 1.0).collect();
+        let y: Vec<f64> = (0..N).map(|i| 0.5 - 0.1 * i as f64).collect();
+        let scale = (1 << 20) as f64;
+        let a = encode_real::<i64, N>(&x, scale);
+        let b = encode_real::<i64, N>(&y, scale);
+        assert_eq!(a.slots, N);
+
+        let decoded = decode_real(a);
+        assert_eq!(decoded.len(), N);
+        for (u, v) in decoded.iter().zip(&x) {
+            assert!((u - v).abs() < 1e-4);
+        }
+
+        // 積はスロットごとの積になる
+        for (i, u) in decode_real(a * b).iter().enumerate() {
+            assert!((u - x[i] * y[i]).abs() < 1e-4);
+        }
+
+        // X -> X^{5^k} で巡回シフトし、X -> X^{-1} では変わらない
+        let ring = Encoding::Real.ring();
+        let m = ModPoly::new(a.m.coeffs, 1 << 40);
+        assert_eq!(m.automorphism_in(4 * N - 1, ring).coeffs, m.coeffs);
+        for k in 0..N {
+            let rotated = m.automorphism_in(real_galois_element::<N>(k), ring);
+            let rotated = decode_real(Plaintext {
+                m: Poly::new(rotated.coeffs),
+                ..a
+            });
+            for (i, u) in rotated.iter().enumerate() {
+                assert!((u - x[(i + k) % N]).abs() < 1e-4);
+            }
+        }
+
+        // sparse packing (n | N)
+        let plaintext = encode_real::<i64, N>(&x[..4], scale);
+        for (u, v) in decode_real(plaintext).iter().zip(&x) {
+            assert!((u - v).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use super::{ciphertext::Ciphertext, evaluator::Evaluator, polyeval::Polynomial};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::fmt::Debug;
+
+// sign(x) (x ∈ [-1, 1]) を f_n^{f} ∘ g_n^{g} で近似する
+// g_n は 0 付近を素早く ±1 に近づけ、f_n は ±1 に収束させる
+#[derive(Debug, Clone, Copy)]
+pub struct SignConfig {
+    // f_n, g_n の次数 2n+1 (g_n は n <= 3)
+    pub n: usize,
+    pub g_iterations: u32,
+    pub f_iterations: u32,
+}
+
+impl Default for SignConfig {
+    // |x| >= 0.2 で誤差 1e-2 程度、深さ 6
+    fn default() -> Self {
+        Self {
+            n: 3,
+            g_iterations: 1,
+            f_iterations: 1,
+        }
+    }
+}
+
+impl SignConfig {
+    // sign が消費するレベル数
+    pub fn depth(&self) -> u32 {
+        (self.g_iterations + self.f_iterations) * f_polynomial(self.n).depth()
+    }
+
+    fn polynomials(&self) -> Vec<Polynomial> {
+        let g = (0..self.g_iterations).map(|_| g_polynomial(self.n));
+        let f = (0..self.f_iterations).map(|_| f_polynomial(self.n));
+        g.chain(f).collect()
+    }
+}
+
+// f_n(x) = Σ_{i=0}^n (1/4^i) C(2i, i) x (1 - x^2)^i
+pub fn f_polynomial(n: usize) -> Polynomial {
+    let mut coeffs = vec![0.0; 2 * n + 2];
+    let mut weight = 1.0;
+    for i in 0..=n {
+        if i > 0 {
+            // C(2i, i)/4^i = C(2i-2, i-1)/4^{i-1} * (2i-1)/(2i)
+            weight *= (2 * i - 1) as f64 / (2 * i) as f64;
+        }
+        let mut binomial = 1.0;
+        for j in 0..=i {
+            let sign = if j % 2 == 0 { 1.0 } else { -1.0 };
+            coeffs[2 * j + 1] += weight * binomial * sign;
+            binomial = binomial * (i - j) as f64 / (j + 1) as f64;
+        }
+    }
+
+    Polynomial::monomial(coeffs)
+}
+
+// Cheon, Kim, Kim (2020) の g_n (係数は 1/2^10 単位)
+pub fn g_polynomial(n: usize) -> Polynomial {
+    let coeffs: &[f64] = match n {
+        1 => &[0.0, 2126.0, 0.0, -1359.0],
+        2 => &[0.0, 3334.0, 0.0, -6108.0, 0.0, 3796.0],
+        3 => &[0.0, 4589.0, 0.0, -16577.0, 0.0, 25614.0, 0.0, -12860.0],
+        _ => panic!("g_{} is not available", n),
+    };
+
+    Polynomial::monomial(coeffs.iter().map(|c| c / 1024.0).collect())
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    // sign(x) (x ∈ [-1, 1])
+    pub fn sign(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        self.sign_affine(ciphertext, 1.0, 0.0)
+    }
+
+    // a > b なら 1, a < b なら 0 (a, b ∈ [0, 1])
+    pub fn compare(&self, a: &Ciphertext<T, N>, b: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        self.sign_affine(&self.sub(a, b), 0.5, 0.5)
+    }
+
+    // max(a, b) = (a + b)/2 + (a - b) sign(a - b)/2 (a, b ∈ [0, 1])
+    pub fn max(&self, a: &Ciphertext<T, N>, b: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let (mean, half_abs) = self.mean_and_half_abs(a, b);
+        self.add(&mean, &half_abs)
+    }
+
+    // min(a, b) = (a + b)/2 - (a - b) sign(a - b)/2 (a, b ∈ [0, 1])
+    pub fn min(&self, a: &Ciphertext<T, N>, b: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let (mean, half_abs) = self.mean_and_half_abs(a, b);
+        self.sub(&mean, &half_abs)
+    }
+
+    fn mean_and_half_abs(
+        &self,
+        a: &Ciphertext<T, N>,
+        b: &Ciphertext<T, N>,
+    ) -> (Ciphertext<T, N>, Ciphertext<T, N>) {
+        let a = self.match_scale(a.clone());
+        let b = self.match_scale(b.clone());
+        let difference = self.sub(&a, &b);
+        let half_sign = self.sign_affine(&difference, 0.5, 0.0);
+
+        (
+            self.mul_const(self.add(&a, &b), 0.5),
+            self.rescale(difference * half_sign),
+        )
+    }
+
+    // α sign(x) + β (α, β は最後の多項式に畳み込んでレベルを節約する)
+    fn sign_affine(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        alpha: f64,
+        beta: f64,
+    ) -> Ciphertext<T, N> {
+        let mut polynomials = self.sign_config.polynomials();
+        let last = polynomials
+            .last_mut()
+            .expect("sign needs at least one iteration");
+        for c in last.coeffs.iter_mut() {
+            *c *= alpha;
+        }
+        last.coeffs[0] += beta;
+
+        polynomials
+            .iter()
+            .fold(ciphertext.clone(), |x, polynomial| {
+                self.evaluate_polynomial(&x, polynomial)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{params::Params, testing::Context};
+    use num_complex::Complex64;
+
+    const N: usize = 8;
+
+    // 深い回路では c1 が大きくならないように p を大きく取る
+    fn params() -> Params<i128> {
+        Params::new(6, 1 << 16, 1 << 18, 1 << 12)
+    }
+
+    fn context() -> Context<N> {
+        Context::new(params(), &[])
+    }
+
+    // rescale ごとに scale がずれる法の列
+    fn context_with_factors() -> Context<N> {
+        let factors = vec![65519, 65543, 65521, 65537, 65531, 65539];
+        Context::new(params().with_factors(factors), &[])
+    }
+
+    fn assert_close(actual: Vec<Complex64>, expected: [f64; N / 2], tolerance: f64) {
+        for (z, x) in actual.iter().zip(expected) {
+            assert!(
+                (z.re - x).abs() < tolerance,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn f_polynomial_matches_closed_form() {
+        // f_1 = (3x - x^3)/2, f_2 = (15x - 10x^3 + 3x^5)/8
+        assert_eq!(f_polynomial(1).coeffs, vec![0.0, 1.5, 0.0, -0.5]);
+        assert_eq!(
+            f_polynomial(2).coeffs,
+            vec![0.0, 1.875, 0.0, -1.25, 0.0, 0.375]
+        );
+    }
+
+    #[test]
+    fn sign() {
+        check_sign(context());
+        check_sign(context_with_factors());
+    }
+
+    fn check_sign(context: Context<N>) {
+        let x = [-0.9, -0.3, 0.25, 0.8];
+        let ciphertext = context.encrypt(&x.map(|x| Complex64::new(x, 0.0)));
+
+        let result = context.evaluator.sign(&ciphertext);
+        let depth = context.evaluator.sign_config.depth();
+        assert_eq!(context.evaluator.level(&result), 6 - depth);
+        assert_close(context.decrypt(&result), x.map(f64::signum), 0.05);
+    }
+
+    #[test]
+    fn compare_max_min() {
+        check_compare_max_min(context());
+        check_compare_max_min(context_with_factors());
+    }
+
+    fn check_compare_max_min(mut context: Context<N>) {
+        // max/min は sign より1段深いので浅い設定にする (|a - b| >= 0.4 を仮定)
+        context.evaluator.sign_config = SignConfig {
+            n: 1,
+            g_iterations: 1,
+            f_iterations: 1,
+        };
+        let a = [0.9, 0.1, 0.7, 0.3];
+        let b = [0.2, 0.6, 0.3, 0.8];
+        let encrypt = |x: [f64; N / 2]| context.encrypt(&x.map(|x| Complex64::new(x, 0.0)));
+        let (ca, cb) = (encrypt(a), encrypt(b));
+        let evaluator = &context.evaluator;
+
+        let expected = std::array::from_fn(|i| a[i].max(b[i]));
+        assert_close(context.decrypt(&evaluator.max(&ca, &cb)), expected, 0.05);
+        let expected = std::array::from_fn(|i| a[i].min(b[i]));
+        assert_close(context.decrypt(&evaluator.min(&ca, &cb)), expected, 0.05);
+        let expected = std::array::from_fn(|i| (a[i] > b[i]) as u8 as f64);
+        assert_close(context.decrypt(&evaluator.compare(&ca, &cb)), expected, 0.1);
+    }
+}
@@ -0,0 +1,272 @@
+use super::{
+    ciphertext::Ciphertext,
+    keys::{KeyId, SecretKey},
+    modulo::ConstantTime,
+    plaintext::{Encoding, Plaintext},
+    poly::{ModPoly, Poly},
+};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::fmt::Debug;
+use zeroize::{Zeroize, Zeroizing};
+
+// 復号するだけの暗号文 (c0, c1) mod 2^bits
+// 係数は bits ビットずつ詰めて持つ
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedCiphertext<const N: usize> {
+    pub bits: u32,
+    pub key_id: KeyId,
+    pub scale: f64,
+    pub slots: usize,
+    pub encoding: Encoding,
+    pub data: Vec<u8>,
+}
+
+impl<const N: usize> CompressedCiphertext<N> {
+    // 詰めたあとのバイト数
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+// c_i -> round(c_i q'/q) mod q' (q' = 2^bits)
+// c0' + c1' s = (q'/q)(c0 + c1 s) + r0 + r1 s (|r_i| <= 1/2) なので
+// 丸め誤差は (1 + ||s||_1)/2 <= (N + 1)/2、scale は Δ q'/q になる
+// q' は丸め誤差が Δ' の 2^{-precision_bits} 以下で、値の範囲 q/2Δ が変わらない最小の 2 冪
+pub fn compress_for_decryption<T, const N: usize>(
+    ciphertext: &Ciphertext<T, N>,
+    precision_bits: u32,
+) -> CompressedCiphertext<N>
+where
+    T: Integer + Default + Copy + Debug + NumCast,
+{
+    assert_eq!(
+        ciphertext.degree(),
+        1,
+        "relinearize before compressing a ciphertext"
+    );
+
+    let q = ciphertext.modulo().to_f64().unwrap();
+    let target_scale = (N + 1) as f64 / 2.0 * 2f64.powi(precision_bits as i32);
+    let bits = (q * target_scale / ciphertext.scale)
+        .log2()
+        .ceil()
+        .min(q.log2().ceil()) as u32;
+    // decrypt_compressed は T のまま mod 2^bits で cmod_ct するので 2^bits <= 2^{BITS-2}
+    let limit = 8 * size_of::<T>() as u32 - 2;
+    assert!(
+        bits <= limit,
+        "modulus 2^{} is too large to decrypt with {}-bit coefficients (at most 2^{})",
+        bits,
+        limit + 2,
+        limit
+    );
+
+    let modulo = ciphertext.modulo().to_i128().unwrap();
+    let values: Vec<i128> = ciphertext
+        .c
+        .iter()
+        .flat_map(|c| c.coeffs)
+        .map(|x| scale_to_power_of_two(x.to_i128().unwrap(), modulo, bits))
+        .collect();
+
+    CompressedCiphertext {
+        bits,
+        key_id: ciphertext.key_id,
+        scale: ciphertext.scale * 2f64.powi(bits as i32) / q,
+        slots: ciphertext.slots,
+        encoding: ciphertext.encoding,
+        data: pack_bits(&values, bits),
+    }
+}
+
+// round(x 2^bits / q) (0 < q < 2^127)
+// x 2^bits は i128 に収まらないことがあるので、商を 1 ビットずつ下ろす
+fn scale_to_power_of_two(x: i128, q: i128, bits: u32) -> i128 {
+    let q = q as u128;
+    let (mut quotient, mut remainder) = (x.unsigned_abs() / q, x.unsigned_abs() % q);
+    for _ in 0..bits {
+        // remainder < q < 2^127 なので 2 倍しても溢れない
+        quotient <<= 1;
+        remainder <<= 1;
+        if remainder >= q {
+            quotient += 1;
+            remainder -= q;
+        }
+    }
+    if 2 * remainder >= q {
+        quotient += 1;
+    }
+
+    x.signum() * quotient as i128
+}
+
+// (c0, c1) mod 2^bits
+pub fn decompress<T, const N: usize>(compressed: &CompressedCiphertext<N>) -> [ModPoly<T, N>; 2]
+where
+    T: Integer + Copy + NumCast,
+{
+    let modulo = T::from(1i128 << compressed.bits).expect("modulus does not fit in T");
+    let values = unpack_bits(&compressed.data, compressed.bits, 2 * N);
+
+    [0, 1].map(|i| {
+        let coeffs = std::array::from_fn(|j| T::from(values[i * N + j]).unwrap());
+        ModPoly::new(coeffs, modulo)
+    })
+}
+
+// c0 + c1 s mod 2^bits
+// decrypt と同じく違う鍵一式の秘密鍵なら panic する
+pub fn decrypt_compressed<T, const N: usize>(
+    compressed: &CompressedCiphertext<N>,
+    secret_key: &SecretKey<T, N>,
+) -> Plaintext<T, N>
+where
+    T: ConstantTime + Zeroize + Default + NumCast,
+{
+    if let Err(error) = compressed.key_id.check(secret_key.id) {
+        panic!("{}", error);
+    }
+
+    let [c0, c1] = decompress(compressed);
+    let s = Zeroizing::new(secret_key.expose_secret().with_modulo(c1.modulo));
+    let m = c0.add_ct(&c1.mul_ct(&s, secret_key.ring));
+
+    Plaintext {
+        slots: compressed.slots,
+        encoding: compressed.encoding,
+        ..Plaintext::new(Poly::new(m.coeffs), compressed.scale)
+    }
+}
+
+// (-2^{bits-1}, 2^{bits-1}] の値を下位 bits ビットずつ little endian で詰める (bits < 128)
+pub(crate) fn pack_bits(values: &[i128], bits: u32) -> Vec<u8> {
+    let mask = u128::MAX >> (128 - bits);
+    let mut data = vec![0u8; (values.len() * bits as usize).div_ceil(8)];
+
+    for (i, &value) in values.iter().enumerate() {
+        let value = value as u128 & mask;
+        for b in 0..bits as usize {
+            let position = i * bits as usize + b;
+            data[position / 8] |= (((value >> b) & 1) as u8) << (position % 8);
+        }
+    }
+    data
+}
+
+// pack_bits の逆 (2^{bits-1} より大きいものは負に戻す、128 ビットなら 2 の補数そのもの)
+pub(crate) fn unpack_bits(data: &[u8], bits: u32, len: usize) -> Vec<i128> {
+    (0..len)
+        .map(|i| {
+            let value = (0..bits as usize).fold(0u128, |value, b| {
+                let position = i * bits as usize + b;
+                value | (((data[position / 8] >> (position % 8)) & 1) as u128) << b
+            });
+            if bits < 128 && value > 1 << (bits - 1) {
+                value.wrapping_sub(1 << bits) as i128
+            } else {
+                value as i128
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        self,
+        params::Params,
+        testing::{Context, Z},
+    };
+
+    #[test]
+    fn pack_roundtrip() {
+        let values = [0, 1, -1, 7, -7, 8, -3];
+        for bits in [4, 5, 13, 63, 127, 128] {
+            let data = pack_bits(&values, bits);
+            assert_eq!(data.len(), (values.len() * bits as usize).div_ceil(8));
+            assert_eq!(unpack_bits(&data, bits, values.len()), values);
+        }
+    }
+
+    #[test]
+    fn compress() {
+        const N: usize = 8;
+        let context = Context::<N>::small(&[]);
+        let evaluator = &context.evaluator;
+
+        let a = context.encrypt(&Z);
+        let squared = evaluator.rescale(a.clone() * a);
+
+        let compressed = compress_for_decryption(&squared, 12);
+        // 範囲 q_2/Δ = 2^36 と scale Δ' = 2^12 (N + 1)/2 で q' = 2^51
+        assert_eq!(compressed.bits, 51);
+        assert!(compressed.size() < 2 * N * size_of::<i128>() / 2);
+
+        // q' = 2^bits は Δ' = Δ q'/q >= (N + 1)/2 * 2^12 となる最小の 2 冪
+        let bound = (N + 1) as f64 / 2.0 * 2f64.powi(12);
+        assert!(bound <= compressed.scale && compressed.scale < 2.0 * bound);
+
+        // 圧縮で増える誤差は係数ごとに m/Δ の 2^{-12} 以下
+        // (1 + ||s||_1)/2 は最悪の場合なので、500 回で最小 13.4 bits、中央値 14.4 bits
+        let decrypted = decrypt_compressed(&compressed, &context.secret_key);
+        let original = ckks::decrypt(squared, &context.secret_key);
+        let error = decrypted
+            .m
+            .coeffs
+            .iter()
+            .zip(original.m.coeffs)
+            .map(|(&a, b)| (a as f64 / decrypted.scale - b as f64 / original.scale).abs())
+            .fold(0.0, f64::max);
+        assert!(-error.log2() >= 12.0);
+
+        // 値の精度は 2 乗したときの誤差 (500 回で最小 9.1 bits、中央値 10.8 bits) で決まり、
+        // 圧縮で 12 bits まで上がることはない
+        let expected = Z.map(|z| z * z);
+        assert!(ckks::precision_bits(&expected, &ckks::decode(decrypted)) > 8.0);
+    }
+
+    #[test]
+    fn scale_to_power_of_two() {
+        // 1000 * 2^4 / 3 = 5333.33..., -5 * 2^2 / 8 = -2.5
+        assert_eq!(super::scale_to_power_of_two(1000, 3, 4), 5333);
+        assert_eq!(super::scale_to_power_of_two(-5, 8, 2), -3);
+        // x 2^bits が i128 を超える
+        let q = (1 << 126) + 1;
+        assert_eq!(super::scale_to_power_of_two(q / 2, q, 126), 1 << 125);
+        assert_eq!(
+            super::scale_to_power_of_two(-q / 3, q, 126),
+            -((1 << 126) / 3)
+        );
+    }
+
+    #[test]
+    fn compress_without_loss() {
+        const N: usize = 8;
+        let context = Context::<N>::small(&[]);
+
+        // q_3 = 2^68 はそのまま q' = 2^68 になり、係数は変わらない
+        // (f64 で丸めると 2^53 を超える係数の下位ビットが落ちる)
+        let ciphertext = context.encrypt(&Z);
+        let compressed = compress_for_decryption(&ciphertext, 60);
+        assert_eq!(compressed.bits, 68);
+        for (c, original) in decompress::<i128, N>(&compressed).iter().zip(&ciphertext.c) {
+            assert_eq!(c.coeffs, original.coeffs);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "too large to decrypt with 64-bit coefficients")]
+    fn compress_rejects_wide_modulus() {
+        const N: usize = 4;
+        let params = Params::new(3, 1000, 1000, 1000);
+        let (_, _, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+
+        // q = 3 * 2^61 なので q' = 2^63 になり、i64 の cmod_ct では扱えない
+        let q = 3 << 61;
+        let c = ModPoly::new([1, 2, 3, 4], q);
+        let ciphertext = Ciphertext::new(c, c, evaluation_key, 1.0);
+        let _ = compress_for_decryption(&ciphertext, 60);
+    }
+}
@@ -0,0 +1,430 @@
+use super::{
+    code::{real_galois_element, root_exponent},
+    params::Params,
+    plaintext::{Encoding, Plaintext},
+    poly::Poly,
+};
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::{f64::consts::PI, fmt};
+
+// σ^{-1}(z) の虚部はこれ以下なら丸め誤差とみなす
+const IMAGINARY_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+    // スロット数が N/2 (実数スロットなら N) を割り切らない
+    InvalidSlots {
+        slots: usize,
+        max: usize,
+    },
+    // 係数が N 個より多い
+    TooManyCoefficients {
+        len: usize,
+        max: usize,
+    },
+    // σ^{-1}(z) の index 番目の係数の虚部が消えない
+    ImaginaryResidue {
+        index: usize,
+        residue: f64,
+    },
+    // 実数スロットに入れる値の index 番目に虚部がある
+    NotReal {
+        index: usize,
+        imaginary: f64,
+    },
+    // 平文の符号化が違う
+    EncodingMismatch {
+        expected: Encoding,
+        found: Encoding,
+    },
+    // round(c * scale) が T で表せない
+    Unrepresentable {
+        index: usize,
+        value: f64,
+    },
+    // |m_index| が bound を超える (次の掛け算で法を溢れる)
+    Overflow {
+        index: usize,
+        value: f64,
+        bound: f64,
+    },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidSlots { slots, max } => {
+                write!(f, "number of slots {} must divide {}", slots, max)
+            }
+            Self::TooManyCoefficients { len, max } => {
+                write!(f, "{} coefficients given but at most {} fit", len, max)
+            }
+            Self::ImaginaryResidue { index, residue } => write!(
+                f,
+                "coefficient {} has imaginary residue {:e} (> {:e})",
+                index, residue, IMAGINARY_TOLERANCE
+            ),
+            Self::NotReal { index, imaginary } => write!(
+                f,
+                "value {} has imaginary part {:e} but the slots are real",
+                index, imaginary
+            ),
+            Self::EncodingMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected a {:?} plaintext but found {:?}",
+                    expected, found
+                )
+            }
+            Self::Unrepresentable { index, value } => {
+                write!(
+                    f,
+                    "coefficient {} ({:e}) does not fit in the integer type",
+                    index, value
+                )
+            }
+            Self::Overflow {
+                index,
+                value,
+                bound,
+            } => write!(
+                f,
+                "coefficient {} ({:e}) exceeds {:e} and would overflow the modulus",
+                index, value, bound
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+// 1の原始 2N 乗根のべきとスロットの順序を前計算しておく
+#[derive(Debug, Clone)]
+pub struct Encoder<T, const N: usize> {
+    // roots[k] = ξ^k (k < 2N)
+    roots: Vec<Complex64>,
+    // i 番目のスロットは ξ^{exponents[i]} での値
+    exponents: Vec<usize>,
+    // 実数スロット用 (ζ は 1の原始 4N 乗根)
+    // cosines[r] = cos(πr/2N) = (ζ^r + ζ^{-r})/2 (r < 4N)
+    cosines: Vec<f64>,
+    // k 番目の実数スロットは ζ^{real_exponents[k]} での値 (5^k mod 4N)
+    real_exponents: Vec<usize>,
+    pub scale: f64,
+    // Some(q) なら |m|∞ * headroom <= q/2 を確かめる
+    pub modulo: Option<T>,
+    // 既定は scale (もう1回 scale を掛けても溢れない)
+    pub headroom: f64,
+}
+
+impl<T: Integer + NumCast + Copy + Default, const N: usize> Encoder<T, N> {
+    pub fn new(scale: f64) -> Self {
+        let roots = (0..2 * N)
+            .map(|k| Complex64::from_polar(1.0, PI * k as f64 / N as f64))
+            .collect();
+        let exponents = (0..N).map(root_exponent::<N>).collect();
+        let cosines = (0..4 * N)
+            .map(|r| (PI * r as f64 / (2 * N) as f64).cos())
+            .collect();
+        let real_exponents = (0..N).map(real_galois_element::<N>).collect();
+
+        Self {
+            roots,
+            exponents,
+            cosines,
+            real_exponents,
+            scale,
+            modulo: None,
+            headroom: scale,
+        }
+    }
+
+    // 法 q_l の暗号文と組み合わせる平文を作る
+    pub fn with_modulo(self, modulo: T) -> Self {
+        Self {
+            modulo: Some(modulo),
+            ..self
+        }
+    }
+
+    // レベル l の暗号文と組み合わせる平文を作る (scale Δ_l, 法 q_l)
+    pub fn at_level(params: &Params<T>, level: u32) -> Self {
+        Self::new(params.level_scale(level)).with_modulo(params.modulo(level))
+    }
+
+    // 掛け算の予定がなければ 1 にしてよい
+    pub fn with_headroom(self, headroom: f64) -> Self {
+        Self { headroom, ..self }
+    }
+
+    // ℂ^n -> ℤ[X]/(X^N + 1) (n | N/2)
+    // n < N/2 のときは z を周期 n で N/2 個に並べて符号化する (sparse packing)
+    // このとき m は部分環 ℤ[X^{N/2n}] に入る
+    pub fn encode(&self, z: &[Complex64]) -> Result<Plaintext<T, N>, EncodeError> {
+        let slots = z.len();
+        if slots == 0 || !(N / 2).is_multiple_of(slots) {
+            return Err(EncodeError::InvalidSlots { slots, max: N / 2 });
+        }
+
+        // [z, conj(z) の逆順]
+        let w: Vec<Complex64> = (0..N)
+            .map(|j| {
+                if j < N / 2 {
+                    z[j % slots]
+                } else {
+                    z[(N - 1 - j) % slots].conj()
+                }
+            })
+            .collect();
+
+        // σ^{-1}(w)_i = (1/N) Σ_j w_j conj(ξ^{i e_j})
+        let mut coeffs = [0.0; N];
+        for (i, c) in coeffs.iter_mut().enumerate() {
+            let sum: Complex64 = w
+                .iter()
+                .zip(&self.exponents)
+                .map(|(wj, &e)| wj * self.roots[i * e % (2 * N)].conj())
+                .sum::<Complex64>()
+                / N as f64;
+            if sum.im.is_nan() || sum.im.abs() > IMAGINARY_TOLERANCE {
+                return Err(EncodeError::ImaginaryResidue {
+                    index: i,
+                    residue: sum.im,
+                });
+            }
+            *c = sum.re;
+        }
+
+        Ok(Plaintext {
+            slots,
+            ..self.round(&coeffs)?
+        })
+    }
+
+    // ℤ[X]/(X^N + 1) -> ℂ^n (n = plaintext.slots)
+    pub fn decode(&self, plaintext: &Plaintext<T, N>) -> Result<Vec<Complex64>, EncodeError> {
+        expect_encoding(plaintext, Encoding::Slots)?;
+        Ok(self.evaluate(plaintext))
+    }
+
+    // ℝ^n -> ℤ[X + X^{-1}] ⊂ ℤ[X]/(X^{2N} + 1) (n | N)
+    // 係数は基底 1, X^j + X^{-j} (1 <= j < N) についてのもの
+    // m(ζ^e) = m_0 + Σ_j 2 m_j cos(πje/2N) が実数なので、e = 5^k (k < N) の N 個すべてがスロットになる
+    // n < N のときは x を周期 n で N 個に並べて符号化する
+    pub fn encode_real(&self, x: &[f64]) -> Result<Plaintext<T, N>, EncodeError> {
+        let slots = x.len();
+        if slots == 0 || !N.is_multiple_of(slots) {
+            return Err(EncodeError::InvalidSlots { slots, max: N });
+        }
+
+        // Σ_k cos(πie_k/2N) cos(πje_k/2N) = N/2 δ_ij (1 <= i, j < N) より
+        // m_0 = (1/N) Σ_k x_k, m_j = (1/N) Σ_k x_k cos(πje_k/2N)
+        let mut coeffs = [0.0; N];
+        for (j, c) in coeffs.iter_mut().enumerate() {
+            let sum: f64 = self
+                .real_exponents
+                .iter()
+                .enumerate()
+                .map(|(k, &e)| x[k % slots] * self.cosines[j * e % (4 * N)])
+                .sum();
+            *c = sum / N as f64;
+        }
+
+        Ok(Plaintext {
+            slots,
+            encoding: Encoding::Real,
+            ..self.round(&coeffs)?
+        })
+    }
+
+    // ℤ[X + X^{-1}] -> ℝ^n (n = plaintext.slots)
+    pub fn decode_real(&self, plaintext: &Plaintext<T, N>) -> Result<Vec<f64>, EncodeError> {
+        expect_encoding(plaintext, Encoding::Real)?;
+        let scale = plaintext.scale;
+
+        Ok(self.real_exponents[..plaintext.slots]
+            .iter()
+            .map(|&e| {
+                let sum: f64 = plaintext
+                    .m
+                    .coeffs
+                    .iter()
+                    .enumerate()
+                    .map(|(j, c)| {
+                        let weight = if j == 0 { 1.0 } else { 2.0 };
+                        weight * c.to_f64().unwrap() * self.cosines[j * e % (4 * N)]
+                    })
+                    .sum();
+                sum / scale
+            })
+            .collect())
+    }
+
+    // ℝ^k -> ℤ[X]/(X^N + 1) (k <= N)
+    // 係数をそのまま scale 倍して丸める (canonical embedding を通さない)
+    pub fn encode_coeffs(&self, coeffs: &[f64]) -> Result<Plaintext<T, N>, EncodeError> {
+        if coeffs.len() > N {
+            return Err(EncodeError::TooManyCoefficients {
+                len: coeffs.len(),
+                max: N,
+            });
+        }
+        let coeffs: [f64; N] = std::array::from_fn(|i| coeffs.get(i).copied().unwrap_or_default());
+
+        Ok(Plaintext {
+            encoding: Encoding::Coefficients,
+            ..self.round(&coeffs)?
+        })
+    }
+
+    // ℤ[X]/(X^N + 1) -> ℝ^N
+    pub fn decode_coeffs(&self, plaintext: &Plaintext<T, N>) -> Result<Vec<f64>, EncodeError> {
+        expect_encoding(plaintext, Encoding::Coefficients)?;
+        let scale = plaintext.scale;
+
+        Ok(plaintext
+            .m
+            .coeffs
+            .iter()
+            .map(|x| x.to_f64().unwrap() / scale)
+            .collect())
+    }
+
+    // round(c * scale)
+    // 法が決まっていれば |m|∞ * headroom <= q/2 を確かめる
+    fn round(&self, coeffs: &[f64; N]) -> Result<Plaintext<T, N>, EncodeError> {
+        let scale = self.scale;
+        let bound = self
+            .modulo
+            .map(|q| q.to_f64().unwrap() / 2.0 / self.headroom);
+
+        let mut m = [T::zero(); N];
+        for (index, (m, c)) in m.iter_mut().zip(coeffs).enumerate() {
+            let value = (c * scale).round();
+            if let Some(bound) = bound
+                && value.abs() > bound
+            {
+                return Err(EncodeError::Overflow {
+                    index,
+                    value,
+                    bound,
+                });
+            }
+            *m = T::from(value).ok_or(EncodeError::Unrepresentable { index, value })?;
+        }
+
+        Ok(Plaintext {
+            modulo: self.modulo,
+            ..Plaintext::new(Poly::new(m), self.scale)
+        })
+    }
+
+    // 最初の slots 個のスロットでの値 m(ξ^{e_j}) / scale
+    fn evaluate(&self, plaintext: &Plaintext<T, N>) -> Vec<Complex64> {
+        let scale = plaintext.scale;
+
+        self.exponents[..plaintext.slots]
+            .iter()
+            .map(|&e| {
+                plaintext
+                    .m
+                    .coeffs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| self.roots[i * e % (2 * N)] * c.to_f64().unwrap())
+                    .sum::<Complex64>()
+                    / scale
+            })
+            .collect()
+    }
+}
+
+fn expect_encoding<T: Integer, const N: usize>(
+    plaintext: &Plaintext<T, N>,
+    expected: Encoding,
+) -> Result<(), EncodeError> {
+    if plaintext.encoding != expected {
+        return Err(EncodeError::EncodingMismatch {
+            expected,
+            found: plaintext.encoding,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        code::{canonical_embedding_inv, project_inv},
+        testing::Z,
+    };
+
+    const N: usize = 8;
+
+    #[test]
+    fn matches_canonical_embedding() {
+        let encoder = Encoder::<i64, N>::new((1 << 20) as f64);
+        let plaintext = encoder.encode(&Z).unwrap();
+
+        let expected = canonical_embedding_inv::<N>(project_inv(Z));
+        for (m, c) in plaintext.m.coeffs.iter().zip(expected.coeffs) {
+            assert_eq!(*m, (c.re * (1 << 20) as f64).round() as i64);
+        }
+        let decoded = encoder.decode(&plaintext).unwrap();
+        assert!(crate::ckks::precision_bits(&Z, &decoded) > 15.0);
+    }
+
+    #[test]
+    fn errors() {
+        let encoder = Encoder::<i64, N>::new((1 << 20) as f64);
+
+        let z = [Complex64::default(); 3];
+        assert_eq!(
+            encoder.encode(&z).unwrap_err(),
+            EncodeError::InvalidSlots { slots: 3, max: 4 }
+        );
+
+        let z = [Complex64::new(f64::NAN, 0.0); 4];
+        assert!(matches!(
+            encoder.encode(&z),
+            Err(EncodeError::ImaginaryResidue { index: 0, .. })
+        ));
+
+        let plaintext = encoder.encode_coeffs(&[1.0; N]).unwrap();
+        assert_eq!(
+            encoder.decode(&plaintext).unwrap_err(),
+            EncodeError::EncodingMismatch {
+                expected: Encoding::Slots,
+                found: Encoding::Coefficients
+            }
+        );
+        assert!(encoder.encode_coeffs(&[1.0; N + 1]).is_err());
+    }
+
+    #[test]
+    fn overflow() {
+        // |m|∞ * 2^12 <= 2^30 / 2 なら通る
+        let encoder = Encoder::<i64, N>::new(4096.0).with_modulo(1 << 30);
+        assert!(encoder.encode_coeffs(&[0.3, -1.0, 30.0]).is_ok());
+        assert_eq!(
+            encoder.encode_coeffs(&[0.3, -40.0]).unwrap_err(),
+            EncodeError::Overflow {
+                index: 1,
+                value: -163840.0,
+                bound: 131072.0
+            }
+        );
+        // 掛け算しないなら q/2 まで使える
+        let encoder = encoder.with_headroom(1.0);
+        assert!(encoder.encode_coeffs(&[0.3, -40.0]).is_ok());
+
+        let encoder = Encoder::<i64, N>::new(2f64.powi(40));
+        assert!(matches!(
+            encoder.encode_coeffs(&[1e10]),
+            Err(EncodeError::Unrepresentable { index: 0, .. })
+        ));
+    }
+}
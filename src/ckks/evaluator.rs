@@ -0,0 +1,323 @@
+use super::{
+    bootstrap::BootstrapConfig,
+    ciphertext::Ciphertext,
+    code::{conjugation_element, galois_element, real_galois_element},
+    compare::SignConfig,
+    keys::{GaloisKey, KeyMismatch},
+    params::Params,
+    plaintext::{Encoding, Plaintext, scales_match},
+    poly::{Poly, Ring},
+};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::{collections::HashMap, fmt::Debug};
+
+// 回転などの鍵が必要な準同型演算をまとめる
+#[derive(Debug, Clone)]
+pub struct Evaluator<T: Integer, const N: usize> {
+    pub params: Params<T>,
+    // g -> τ_g(s) から s への鍵
+    pub galois_keys: HashMap<usize, GaloisKey<T, N>>,
+    pub bootstrap_config: BootstrapConfig,
+    pub sign_config: SignConfig,
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    pub fn new(params: Params<T>, galois_keys: Vec<GaloisKey<T, N>>) -> Self {
+        Self {
+            params,
+            galois_keys: galois_keys.into_iter().map(|key| (key.g, key)).collect(),
+            bootstrap_config: BootstrapConfig::default(),
+            sign_config: SignConfig::default(),
+        }
+    }
+
+    // q_l = q0 * f_1 * ... * f_l の l
+    pub fn level(&self, ciphertext: &Ciphertext<T, N>) -> u32 {
+        self.params.level(ciphertext.modulo())
+    }
+
+    // 今のレベルの f_l で割る
+    pub fn rescale(&self, ciphertext: Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let factor = self.factor(&ciphertext);
+        ciphertext.rescale(factor)
+    }
+
+    // scale を変えずに法だけ q_level に下げる
+    pub fn mod_switch_to(&self, ciphertext: Ciphertext<T, N>, level: u32) -> Ciphertext<T, N> {
+        ciphertext.mod_switch_to(&self.params, level)
+    }
+
+    pub fn mod_switch_to_next(&self, ciphertext: Ciphertext<T, N>) -> Ciphertext<T, N> {
+        ciphertext.mod_switch_to_next(&self.params)
+    }
+
+    fn factor(&self, ciphertext: &Ciphertext<T, N>) -> T {
+        self.params.factor(self.level(ciphertext))
+    }
+
+    // X -> X^g (共役不変環なら g は 4N を法として考える)
+    // Galois 鍵が違う鍵一式のものなら panic する (try_apply_galois はエラーを返す)
+    pub fn apply_galois(&self, ciphertext: &Ciphertext<T, N>, g: usize) -> Ciphertext<T, N> {
+        self.try_apply_galois(ciphertext, g)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_apply_galois(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        g: usize,
+    ) -> Result<Ciphertext<T, N>, KeyMismatch> {
+        let g = g % ciphertext.ring().galois_order::<N>();
+        if g == 1 {
+            return Ok(ciphertext.clone());
+        }
+
+        let galois_key = self.galois_key(g);
+        ciphertext.key_id.check(galois_key.key.id)?;
+        Ok(ciphertext.clone().automorphism(galois_key))
+    }
+
+    fn galois_key(&self, g: usize) -> &GaloisKey<T, N> {
+        self.galois_keys
+            .get(&g)
+            .unwrap_or_else(|| panic!("missing Galois key for X -> X^{}", g))
+    }
+
+    // X -> X^{5^k}
+    pub fn rotate(&self, ciphertext: &Ciphertext<T, N>, k: usize) -> Ciphertext<T, N> {
+        let g = match ciphertext.ring() {
+            Ring::Negacyclic => galois_element::<N>(k),
+            Ring::ConjugateInvariant => real_galois_element::<N>(k),
+        };
+        self.apply_galois(ciphertext, g)
+    }
+
+    // X -> X^{-1}
+    // 実数スロットの暗号文は共役で変わらないので鍵も要らない
+    pub fn conjugate(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        if ciphertext.encoding == Encoding::Real {
+            return ciphertext.clone();
+        }
+
+        self.apply_galois(ciphertext, conjugation_element::<N>())
+    }
+
+    // scale を target に合わせる (1レベル使う)
+    // 1 を scale c = round(target f_l / s) で符号化して掛けると s c / f_l ≈ target になる
+    pub fn adjust_scale(&self, ciphertext: Ciphertext<T, N>, target: f64) -> Ciphertext<T, N> {
+        let f = self.factor(&ciphertext).to_f64().unwrap();
+        let factor = (target * f / ciphertext.scale).round();
+        assert!(
+            factor >= 1.0,
+            "cannot adjust scale {} to {}",
+            ciphertext.scale,
+            target
+        );
+
+        // c は整数なので s c / f_l は target から相対誤差 1/(2c) だけずれる
+        // 値の誤差 |z|/(2c) は rescale の丸め誤差と同程度なので、scale は target とする
+        let encoding = ciphertext.encoding;
+        Ciphertext {
+            scale: target,
+            ..self.rescale(ciphertext * constant(1.0, factor, encoding))
+        }
+    }
+
+    // a + b (scale がずれていれば法の大きい方を adjust_scale で合わせる)
+    pub fn add(&self, a: &Ciphertext<T, N>, b: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let (a, b) = self.align_scales(a.clone(), b.clone());
+        a + b
+    }
+
+    // a - b (scale は add と同様に合わせる)
+    pub fn sub(&self, a: &Ciphertext<T, N>, b: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let (a, b) = self.align_scales(a.clone(), b.clone());
+        a - b
+    }
+
+    fn align_scales(
+        &self,
+        a: Ciphertext<T, N>,
+        b: Ciphertext<T, N>,
+    ) -> (Ciphertext<T, N>, Ciphertext<T, N>) {
+        if scales_match(a.scale, b.scale) {
+            return (a, b);
+        }
+
+        if a.modulo() >= b.modulo() {
+            let target = b.scale;
+            (self.adjust_scale(a, target), b)
+        } else {
+            let target = a.scale;
+            (a, self.adjust_scale(b, target))
+        }
+    }
+
+    // c + a (全スロットに実数 a を足す)
+    pub fn add_const(&self, ciphertext: Ciphertext<T, N>, a: f64) -> Ciphertext<T, N> {
+        let scale = ciphertext.scale;
+        let encoding = ciphertext.encoding;
+        ciphertext + constant(a, scale, encoding)
+    }
+
+    // c * a (全スロットに実数 a を掛けて rescale する)
+    // a を scale f_l で符号化するので scale は変わらない
+    pub fn mul_const(&self, ciphertext: Ciphertext<T, N>, a: f64) -> Ciphertext<T, N> {
+        let encoding = ciphertext.encoding;
+        let f = self.factor(&ciphertext).to_f64().unwrap();
+        self.rescale(ciphertext * constant(a, f, encoding))
+    }
+}
+
+// 全スロットが a の平文は定数多項式 a (周期 1 なのでどのスロット数とも組み合わせられる)
+// 係数の符号化でも定数項 a なので、相手の暗号文の符号化に合わせる
+pub(crate) fn constant<T: Integer + NumCast + Default + Copy, const N: usize>(
+    a: f64,
+    scale: f64,
+    encoding: Encoding,
+) -> Plaintext<T, N> {
+    let mut coeffs = [T::zero(); N];
+    coeffs[0] = T::from((a * scale).round()).unwrap();
+
+    Plaintext {
+        slots: 1,
+        encoding,
+        ..Plaintext::new(Poly::new(coeffs), scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        self,
+        testing::{Context, Z, small_params},
+    };
+    use num_complex::Complex64;
+
+    #[test]
+    fn real_slots() {
+        const N: usize = 8;
+        let params =
+            Params::<i128>::new(2, 1 << 16, 1 << 20, 1 << 16).with_ring(Ring::ConjugateInvariant);
+        // 共役の鍵は作らない
+        let context = Context::<N>::new(params.clone(), &[real_galois_element::<N>(1)]);
+        let evaluator = &context.evaluator;
+
+        // N 個の実数が入る
+        // 誤差は 500 回で最大 2.0e-3 (暗号化)、3.4e-3 (2 乗)、2.6e-3 (回転)
+        let x = [0.5, -0.75, 0.3, -0.2, 0.9, -0.1, 0.0, 0.65];
+        let plaintext = ckks::encode_real::<i128, N>(&x, params.p as f64);
+        let ciphertext = ckks::encrypt(plaintext, context.public_key, context.evaluation_key);
+
+        let decoded = ckks::decode_real(ckks::decrypt(ciphertext.clone(), &context.secret_key));
+        assert_eq!(decoded.len(), N);
+        for (a, b) in decoded.iter().zip(x) {
+            assert!((a - b).abs() < 1e-2);
+        }
+
+        let squared = evaluator.rescale(ciphertext.clone() * evaluator.conjugate(&ciphertext));
+        let decoded = ckks::decode_real(ckks::decrypt(squared, &context.secret_key));
+        assert_eq!(decoded.len(), N);
+        for (a, b) in decoded.iter().zip(x) {
+            assert!((a - b * b).abs() < 1e-2);
+        }
+
+        // X -> X^5 で N 個のスロットが巡回シフトする
+        let rotated = evaluator.rotate(&ciphertext, 1);
+        let decoded = ckks::decode_real(ckks::decrypt(rotated, &context.secret_key));
+        for (i, a) in decoded.iter().enumerate() {
+            assert!((a - x[(i + 1) % N]).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn add_with_drifted_scales() {
+        const N: usize = 8;
+        let params = small_params();
+        let context = Context::<N>::new(params.clone(), &[]);
+        let evaluator = &context.evaluator;
+        let p = params.p as f64;
+
+        let a = context.encrypt(&Z);
+        // 1 を p + 3 で符号化して掛けると rescale 後の scale は p + 3 になる
+        let drifted = evaluator.rescale(a.clone() * constant(1.0, p + 3.0, Encoding::Slots));
+        assert_eq!(drifted.scale, p + 3.0);
+        assert!(!scales_match(drifted.scale, a.scale));
+
+        // 精度は鍵と暗号化の誤差で決まる (500 回で最小 9.2 bits、中央値 10.4 bits)
+        let sum = evaluator.add(&drifted, &a);
+        let expected = Z.map(|z| 2.0 * z);
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&sum)) > 8.0);
+
+        // a を合わせると drifted と同じ暗号文になるので、別に暗号化した b から引く
+        // (500 回で最小 9.6 bits、中央値 11.0 bits)
+        let b = context.encrypt(&Z);
+        let difference = evaluator.sub(&b, &drifted);
+        assert!(
+            ckks::precision_bits(&[Complex64::default(); 4], &context.decrypt(&difference)) > 8.0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "differ")]
+    fn add_rejects_drifted_scales() {
+        const N: usize = 8;
+        let params = small_params();
+        let context = Context::<N>::new(params.clone(), &[]);
+        let evaluator = &context.evaluator;
+
+        let a = context.encrypt(&[Complex64::new(0.5, 0.0); 4]);
+        let drifted =
+            evaluator.rescale(a.clone() * constant(1.0, params.p as f64 + 3.0, Encoding::Slots));
+        let _ = drifted + a;
+    }
+
+    #[test]
+    fn exact_scales() {
+        const N: usize = 8;
+        let conventional = small_params().with_factors(vec![65537, 65539, 65543]);
+        let exact = conventional.clone().with_exact_scales();
+        // Δ_{l-1} = Δ_l^2 / f_l
+        for l in 1..=3 {
+            let expected = exact.level_scale(l).powi(2) / exact.factor(l) as f64;
+            assert!(scales_match(exact.level_scale(l - 1), expected));
+        }
+
+        let expected = Z.map(|z| z.powi(4) + z.powi(3));
+        // 同じ入力で x^4 + x^3 を計算する (x^3 と x^4 はどちらもレベル 1)
+        let evaluate = |params: &Params<i128>| {
+            let context = Context::<N>::new(params.clone(), &[]);
+            let evaluator = &context.evaluator;
+            let x = context.encrypt(&Z);
+            let x2 = evaluator.rescale(x.clone() * x.clone());
+            let x3 = evaluator.rescale(x2.clone() * evaluator.mod_switch_to_next(x));
+            let x4 = evaluator.rescale(x2.clone() * x2);
+            assert_eq!(evaluator.level(&x3), 1);
+            assert_eq!(evaluator.level(&x4), 1);
+
+            let sum = evaluator.add(&x4, &x3);
+            let bits = ckks::precision_bits(&expected, &context.decrypt(&sum));
+            (
+                scales_match(x3.scale, x4.scale),
+                evaluator.level(&sum),
+                bits,
+            )
+        };
+
+        // 精度はどちらも 300 回で最小 8.6 bits、中央値 10.2〜10.3 bits で、違いは使うレベル数
+        // 経路によらずレベル 1 の scale は Δ_1 なので、そのまま足せる
+        let (matched, level, bits) = evaluate(&exact);
+        assert!(matched);
+        assert_eq!(level, 1);
+        assert!(bits > 8.0);
+
+        // 従来の scale では x^3 は p^3/(f_3 f_2)、x^4 は p^4/(f_3^2 f_2) でずれるので、
+        // adjust_scale で合わせるのに1レベル余計に使う
+        let (matched, level, bits) = evaluate(&conventional);
+        assert!(!matched);
+        assert_eq!(level, 0);
+        assert!(bits > 8.0);
+    }
+}
@@ -1,63 +1,594 @@
-use std::ops::Neg;
+use std::{fmt, ops::Neg};
 
 use num_integer::Integer;
+use num_traits::NumCast;
 use rand::distr::uniform::SampleUniform;
+use zeroize::{Zeroize, Zeroizing};
 
-use super::poly::ModPoly;
+use super::{
+    generate_galois_keys, generate_keys_for,
+    modulo::ConstantTime,
+    params::{Params, fnv1a},
+    poly::{ModPoly, Ring},
+};
 
-#[derive(Debug, Clone, Copy)]
-pub struct SecretKey<T: Integer, const N: usize> {
-    pub s: ModPoly<T, N>,
+// 鍵と暗号化の誤差 e の係数は [-ERROR_BOUND, ERROR_BOUND)
+pub const ERROR_BOUND: i64 = 3;
+
+// 鍵一式の識別子 (公開鍵と Params のハッシュ)
+// 鍵と暗号文に付けて、違う鍵一式のものを組み合わせたら KeyMismatch にする
+// 個別に generate した鍵は秘密鍵ごとのランダムな識別子 (0 は鍵のない平文に使う)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyId(pub u64);
+
+impl KeyId {
+    pub fn new<T: Integer + Copy + NumCast, const N: usize>(
+        public_key: &PublicKey<T, N>,
+        params: &Params<T>,
+    ) -> Self {
+        let mut bytes = params.fingerprint().to_le_bytes().to_vec();
+        for x in public_key.b.coeffs.iter().chain(&public_key.a.coeffs) {
+            bytes.extend(x.to_i128().unwrap().to_le_bytes());
+        }
+        Self(fnv1a(&bytes))
+    }
+
+    pub fn random() -> Self {
+        Self(rand::random_range(1..=u64::MAX))
+    }
+
+    pub fn check(self, found: Self) -> Result<(), KeyMismatch> {
+        if self == found {
+            Ok(())
+        } else {
+            Err(KeyMismatch {
+                expected: self,
+                found,
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMismatch {
+    pub expected: KeyId,
+    pub found: KeyId,
+}
+
+impl fmt::Display for KeyMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "key {:016x} does not belong to key set {:016x}",
+            self.found.0, self.expected.0
+        )
+    }
 }
 
-impl<T: Integer, const N: usize> SecretKey<T, N>
+impl std::error::Error for KeyMismatch {}
+
+// Copy にはしない (複製は clone で明示する)
+// drop で s の係数を 0 で上書きし、Debug では s を出さない
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T: num_integer::Integer + Zeroize + Copy + num_traits::NumCast + serde::Serialize",
+        deserialize = "T: num_integer::Integer + Zeroize + Copy + num_traits::NumCast + serde::Deserialize<'de>"
+    ))
+)]
+pub struct SecretKey<T: Integer + Zeroize, const N: usize> {
+    s: ModPoly<T, N>,
+    pub id: KeyId,
+    // s とこの鍵で作る鍵・暗号文の環
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ring: Ring,
+}
+
+impl<T: Integer + Zeroize, const N: usize> SecretKey<T, N> {
+    pub fn from_secret(s: ModPoly<T, N>, id: KeyId) -> Self {
+        Self {
+            s,
+            id,
+            ring: Ring::Negacyclic,
+        }
+    }
+
+    pub fn expose_secret(&self) -> &ModPoly<T, N> {
+        &self.s
+    }
+}
+
+impl<T: Integer + Zeroize, const N: usize> SecretKey<T, N>
 where
-    T: Default + Copy + SampleUniform + From<i64> + Neg,
+    T: Copy + From<i64>,
 {
     pub fn generate(modulo: T) -> Self {
-        let s = ModPoly::<T, N>::new_random((-1).into()..2.into(), modulo);
-        Self { s }
+        Self::generate_in(Ring::Negacyclic, modulo)
+    }
+
+    // 共役不変環なら基底 1, X^j + X^{-j} についての係数を {-1, 0, 1} から取る
+    pub fn generate_in(ring: Ring, modulo: T) -> Self {
+        let s = ModPoly::<T, N>::new_random_ct(-1..2, modulo);
+        Self {
+            s,
+            id: KeyId::random(),
+            ring,
+        }
+    }
+}
+
+impl<T: Integer + Zeroize, const N: usize> fmt::Debug for SecretKey<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretKey")
+            .field("s", &format_args!("<redacted>"))
+            .field("id", &self.id)
+            .field("ring", &self.ring)
+            .finish()
+    }
+}
+
+impl<T: Integer + Zeroize, const N: usize> Drop for SecretKey<T, N> {
+    fn drop(&mut self) {
+        self.s.zeroize();
     }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Serialize",
+        deserialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Deserialize<'de>"
+    ))
+)]
 pub struct PublicKey<T: Integer, const N: usize> {
     pub b: ModPoly<T, N>,
     pub a: ModPoly<T, N>,
+    pub id: KeyId,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ring: Ring,
 }
 
 impl<T: Integer, const N: usize> PublicKey<T, N>
 where
-    T: Default + Copy + SampleUniform + From<i64> + Neg<Output = T>,
+    T: Zeroize + ConstantTime + Default + Copy + SampleUniform + From<i64> + Neg<Output = T>,
 {
-    pub fn generate(secret_key: SecretKey<T, N>, modulo: T) -> Self {
+    pub fn generate(secret_key: &SecretKey<T, N>, modulo: T) -> Self {
         let a = ModPoly::<T, N>::new_random((-100).into()..100.into(), modulo);
-        let e = ModPoly::<T, N>::new_random((-3).into()..3.into(), modulo);
-        let b = -a * secret_key.s + e;
-        Self { b, a }
+        let e = ModPoly::<T, N>::new_random_ct(-ERROR_BOUND..ERROR_BOUND, modulo);
+        let b = (-a).mul_ct(&secret_key.s, secret_key.ring).add_ct(&e);
+        Self {
+            b,
+            a,
+            id: secret_key.id,
+            ring: secret_key.ring,
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Serialize",
+        deserialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Deserialize<'de>"
+    ))
+)]
 pub struct EvaluationKey<T: Integer, const N: usize> {
     pub b: ModPoly<T, N>,
     pub a: ModPoly<T, N>,
+    pub scale: T,
+    pub id: KeyId,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ring: Ring,
 }
 
 impl<T: Integer, const N: usize> EvaluationKey<T, N>
 where
-    T: Default + Copy + SampleUniform + From<i64> + Neg<Output = T>,
+    T: Zeroize + ConstantTime + Default + Copy + SampleUniform + From<i64> + Neg<Output = T>,
 {
-    pub fn generate(secret_key: SecretKey<T, N>, modulo: T, scale: T) -> Self {
+    // modulo は q_L (鍵の法は中で P 倍した P*q_L になる)
+    pub fn generate(secret_key: &SecretKey<T, N>, modulo: T, scale: T) -> Self {
+        let s = Zeroizing::new(secret_key.s.with_modulo(modulo * scale));
+        let target = Zeroizing::new(s.mul_ct(&s, secret_key.ring));
+        Self::generate_switching(secret_key, &target, modulo, scale)
+    }
+
+    // target から s への鍵切り替え鍵
+    // b + a*s = e + target*P (mod P*q)
+    pub fn generate_switching(
+        secret_key: &SecretKey<T, N>,
+        target: &ModPoly<T, N>,
+        modulo: T,
+        scale: T,
+    ) -> Self {
         let modulo_scaled = modulo * scale;
 
-        let s = ModPoly {
-            modulo: modulo_scaled,
-            ..secret_key.s
-        };
+        // s と target は鍵を作り終えたら消す
+        let s = Zeroizing::new(secret_key.s.with_modulo(modulo_scaled));
+        let target = Zeroizing::new(target.with_modulo(modulo_scaled));
+        let target = Zeroizing::new(target.mul_scalar_ct(scale));
         let a = ModPoly::<T, N>::new_random((-100).into()..100.into(), modulo_scaled);
-        let e = ModPoly::<T, N>::new_random((-3).into()..3.into(), modulo_scaled);
-        let b = -a * s + e + (s * s) * scale;
-        Self { b, a }
+        let e = ModPoly::<T, N>::new_random_ct(-ERROR_BOUND..ERROR_BOUND, modulo_scaled);
+        let b = (-a).mul_ct(&s, secret_key.ring).add_ct(&e).add_ct(&target);
+        Self {
+            b,
+            a,
+            scale,
+            id: secret_key.id,
+            ring: secret_key.ring,
+        }
+    }
+}
+
+impl<T: Integer + Default + Copy, const N: usize> EvaluationKey<T, N> {
+    // c*target ≈ d0 + d1*s
+    // c を分解しないので、同じ c を複数の鍵で切り替えるときに前計算して使い回せるものはない
+    pub fn switch(&self, c: ModPoly<T, N>) -> (ModPoly<T, N>, ModPoly<T, N>) {
+        let modulo = c.modulo;
+        let c = c.with_modulo(self.b.modulo);
+        let d0 = (c.mul_in(self.b, self.ring) / self.scale).reduce(modulo);
+        let d1 = (c.mul_in(self.a, self.ring) / self.scale).reduce(modulo);
+
+        (d0, d1)
+    }
+}
+
+// X -> X^g で移った τ_g(s) から s への鍵切り替え鍵
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Serialize",
+        deserialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Deserialize<'de>"
+    ))
+)]
+pub struct GaloisKey<T: Integer, const N: usize> {
+    pub g: usize,
+    pub key: EvaluationKey<T, N>,
+}
+
+impl<T: Integer, const N: usize> GaloisKey<T, N>
+where
+    T: Zeroize + ConstantTime + Default + Copy + SampleUniform + From<i64> + Neg<Output = T>,
+{
+    pub fn generate(secret_key: &SecretKey<T, N>, g: usize, modulo: T, scale: T) -> Self {
+        let target = Zeroizing::new(secret_key.s.automorphism_ct(g, secret_key.ring));
+        let key = EvaluationKey::generate_switching(secret_key, &target, modulo, scale);
+        Self { g, key }
+    }
+}
+
+// 公開する鍵一式
+#[derive(Debug, Clone)]
+pub struct KeySet<T: Integer, const N: usize> {
+    pub params: Params<T>,
+    pub public_key: PublicKey<T, N>,
+    pub evaluation_key: EvaluationKey<T, N>,
+    pub galois_keys: Vec<GaloisKey<T, N>>,
+}
+
+// 各鍵の ||b + a*s - P*target||∞
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseReport {
+    pub public_key: f64,
+    pub evaluation_key: f64,
+    pub galois_keys: Vec<(usize, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyError {
+    Mismatch(KeyMismatch),
+    // 法が Params の q_L (評価鍵は P*q_L) と違う
+    WrongModulus { key: String },
+    // 誤差が ERROR_BOUND を超える (s が違うか鍵が壊れている)
+    Noise { key: String, norm: f64, bound: f64 },
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mismatch(error) => write!(f, "{}", error),
+            Self::WrongModulus { key } => {
+                write!(f, "{} does not use the modulus given by the params", key)
+            }
+            Self::Noise { key, norm, bound } => {
+                write!(f, "{} has error norm {} (> {})", key, norm, bound)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<KeyMismatch> for KeyError {
+    fn from(error: KeyMismatch) -> Self {
+        Self::Mismatch(error)
+    }
+}
+
+impl<T: Integer, const N: usize> KeySet<T, N>
+where
+    T: Zeroize
+        + ConstantTime
+        + Default
+        + Copy
+        + NumCast
+        + SampleUniform
+        + From<i64>
+        + Neg<Output = T>,
+{
+    // (公開する鍵一式, 秘密鍵)
+    pub fn generate(params: Params<T>, elements: &[usize]) -> (Self, SecretKey<T, N>) {
+        let (public_key, secret_key, evaluation_key) = generate_keys_for(&params);
+        let galois_keys = generate_galois_keys(&secret_key, &params, elements);
+
+        let key_set = Self {
+            params,
+            public_key,
+            evaluation_key,
+            galois_keys,
+        };
+        (key_set, secret_key)
+    }
+}
+
+impl<
+    T: Integer + Zeroize + ConstantTime + Default + Copy + NumCast + Neg<Output = T>,
+    const N: usize,
+> KeySet<T, N>
+{
+    pub fn id(&self) -> KeyId {
+        self.public_key.id
+    }
+
+    // 識別子と法が揃っていて、各鍵が s で誤差 ERROR_BOUND 以下に復号できるか確かめる
+    pub fn validate(&self, secret_key: &SecretKey<T, N>) -> Result<NoiseReport, KeyError> {
+        let id = self.id();
+        id.check(secret_key.id)?;
+        id.check(self.evaluation_key.id)?;
+        for galois_key in &self.galois_keys {
+            id.check(galois_key.key.id)?;
+        }
+
+        let ql = self.params.modulo(self.params.limit);
+        let p = self.params.scale;
+        check_modulus("secret key", &[&secret_key.s], ql)?;
+        check_modulus("public key", &[&self.public_key.b, &self.public_key.a], ql)?;
+        let evaluation_key = &self.evaluation_key;
+        check_modulus(
+            "evaluation key",
+            &[&evaluation_key.b, &evaluation_key.a],
+            p * ql,
+        )?;
+        if evaluation_key.scale != p {
+            return Err(KeyError::WrongModulus {
+                key: "evaluation key".to_string(),
+            });
+        }
+
+        let s = &secret_key.s;
+        let ring = secret_key.ring;
+        let zero = ModPoly::new([T::zero(); N], ql);
+        let public_key = error_norm(
+            "public key",
+            &self.public_key.b,
+            &self.public_key.a,
+            s,
+            &zero,
+            ring,
+        )?;
+
+        let s_switch = Zeroizing::new(s.with_modulo(p * ql));
+        let squared = Zeroizing::new(s_switch.mul_ct(&s_switch, ring));
+        let evaluation_key = error_norm(
+            "evaluation key",
+            &evaluation_key.b,
+            &evaluation_key.a,
+            s,
+            &Zeroizing::new(squared.mul_scalar_ct(p)),
+            ring,
+        )?;
+
+        let galois_keys = self
+            .galois_keys
+            .iter()
+            .map(|galois_key| {
+                let key = &galois_key.key;
+                let name = format!("Galois key X -> X^{}", galois_key.g);
+                check_modulus(&name, &[&key.b, &key.a], p * ql)?;
+                let rotated = Zeroizing::new(s_switch.automorphism_ct(galois_key.g, ring));
+                let target = Zeroizing::new(rotated.mul_scalar_ct(p));
+                let norm = error_norm(&name, &key.b, &key.a, s, &target, ring)?;
+                Ok((galois_key.g, norm))
+            })
+            .collect::<Result<_, KeyError>>()?;
+
+        Ok(NoiseReport {
+            public_key,
+            evaluation_key,
+            galois_keys,
+        })
+    }
+}
+
+fn check_modulus<T: Integer + Copy, const N: usize>(
+    key: &str,
+    polys: &[&ModPoly<T, N>],
+    modulo: T,
+) -> Result<(), KeyError> {
+    if polys.iter().all(|poly| poly.modulo == modulo) {
+        Ok(())
+    } else {
+        Err(KeyError::WrongModulus {
+            key: key.to_string(),
+        })
+    }
+}
+
+// ||b + a*s - target||∞ (target の法で計算する)
+fn error_norm<T: ConstantTime + Zeroize + Default + NumCast, const N: usize>(
+    key: &str,
+    b: &ModPoly<T, N>,
+    a: &ModPoly<T, N>,
+    s: &ModPoly<T, N>,
+    target: &ModPoly<T, N>,
+    ring: Ring,
+) -> Result<f64, KeyError> {
+    let modulo = target.modulo;
+    let s = Zeroizing::new(s.with_modulo(modulo));
+    let e = b.add_ct(&a.mul_ct(&s, ring)).sub_ct(target);
+    let norm = e
+        .coeffs
+        .iter()
+        .map(|&x| x.to_f64().unwrap().abs())
+        .fold(0.0, f64::max);
+
+    let bound = ERROR_BOUND as f64;
+    if norm > bound {
+        return Err(KeyError::Noise {
+            key: key.to_string(),
+            norm,
+            bound,
+        });
+    }
+    Ok(norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        self, ciphertext::Ciphertext, code::galois_element, evaluator::Evaluator,
+        serialize::FormatError,
+    };
+    use num_complex::Complex64;
+
+    #[test]
+    fn key_ids() {
+        const N: usize = 4;
+        let params = Params::new(3, 1000, 1000, 1000);
+        let (public_key, secret_key, evaluation_key) = ckks::generate_keys_for::<i64, N>(&params);
+        let (_, other_secret_key, other_evaluation_key) =
+            ckks::generate_keys_for::<i64, N>(&params);
+
+        let id = KeyId::new(&public_key, &params);
+        assert_eq!(secret_key.id, id);
+        assert_eq!(evaluation_key.id, id);
+        assert_ne!(other_secret_key.id, id);
+
+        let z = [Complex64::new(1.5, 0.0), Complex64::new(-2.0, 1.0)];
+        let plaintext = ckks::encode::<i64, N>(&z, 10000.0);
+        let ciphertext = ckks::encrypt(plaintext, public_key, evaluation_key);
+        assert_eq!(ciphertext.key_id, id);
+        let mismatch = KeyMismatch {
+            expected: id,
+            found: other_secret_key.id,
+        };
+        assert_eq!(
+            ckks::try_decrypt(ciphertext.clone(), &other_secret_key).err(),
+            Some(mismatch.clone())
+        );
+        assert!(ckks::try_decrypt(ciphertext.clone(), &secret_key).is_ok());
+
+        // 別の鍵一式で暗号化した暗号文とは掛けられない
+        let other = Ciphertext {
+            key_id: other_secret_key.id,
+            evaluation_key: other_evaluation_key,
+            ..ciphertext.clone()
+        };
+        assert_eq!(
+            ciphertext.clone().try_mul(other).err(),
+            Some(mismatch.clone())
+        );
+
+        let g = galois_element::<N>(1);
+        let galois_keys = ckks::generate_galois_keys(&other_secret_key, &params, &[g]);
+        let evaluator = Evaluator::new(params.clone(), galois_keys);
+        assert_eq!(
+            evaluator.try_apply_galois(&ciphertext, g).err(),
+            Some(mismatch.clone())
+        );
+
+        let bytes = ciphertext.to_bytes(&params).unwrap();
+        assert_eq!(
+            Ciphertext::from_bytes(&bytes, &params, other_evaluation_key).err(),
+            Some(FormatError::KeyMismatch(mismatch))
+        );
+
+        // 個別に作った秘密鍵はそれぞれ別の識別子を持ち、そこから作った鍵が受け継ぐ
+        let secret_key = SecretKey::<i64, N>::generate(1000);
+        let other_secret_key = SecretKey::<i64, N>::generate(1000);
+        assert_ne!(secret_key.id, KeyId::default());
+        assert_ne!(secret_key.id, other_secret_key.id);
+        assert_eq!(PublicKey::generate(&secret_key, 1000).id, secret_key.id);
+        let evaluation_key = EvaluationKey::generate(&secret_key, 1000, 1000);
+        assert_eq!(evaluation_key.id, secret_key.id);
+        assert_eq!(
+            GaloisKey::generate(&secret_key, g, 1000, 1000).key.id,
+            secret_key.id
+        );
+    }
+
+    #[test]
+    fn redacted_debug() {
+        const N: usize = 4;
+        let s = ModPoly::<i64, N>::new([1, -1, 0, 1], 1000);
+        let secret_key = SecretKey::from_secret(s, KeyId(7));
+        assert_eq!(
+            format!("{:?}", secret_key),
+            "SecretKey { s: <redacted>, id: KeyId(7), ring: Negacyclic }"
+        );
+        assert_eq!(secret_key.expose_secret().coeffs, [1, -1, 0, 1]);
+    }
+
+    #[test]
+    fn validate() {
+        const N: usize = 4;
+        let params = Params::new(3, 1000, 1000, 1000);
+        let g = galois_element::<N>(1);
+        let (key_set, secret_key) = KeySet::<i64, N>::generate(params.clone(), &[g]);
+
+        let report = key_set.validate(&secret_key).unwrap();
+        assert!(report.public_key <= ERROR_BOUND as f64);
+        assert!(report.evaluation_key <= ERROR_BOUND as f64);
+        assert_eq!(report.galois_keys.len(), 1);
+        assert_eq!(key_set.evaluation_key.b.modulo, 1000i64.pow(5));
+
+        let (_, other_secret_key) = KeySet::<i64, N>::generate(params.clone(), &[]);
+        assert!(matches!(
+            key_set.validate(&other_secret_key),
+            Err(KeyError::Mismatch(_))
+        ));
+        // 評価鍵の法を P*q_L で渡すと P^2*q_L になってしまう
+        let ql = params.modulo(params.limit);
+        let broken = KeySet {
+            evaluation_key: EvaluationKey::generate(&secret_key, params.scale * ql, params.scale),
+            ..key_set.clone()
+        };
+        assert_eq!(
+            broken.validate(&secret_key),
+            Err(KeyError::WrongModulus {
+                key: "evaluation key".to_string()
+            })
+        );
+
+        // 識別子だけ合わせた別の s (s を複製せず、0 でない係数を 1 つ反転する、全部 0 なら s_0 = 1)
+        let mut forged = secret_key;
+        let s = &mut forged.s;
+        match s.coeffs.iter().position(|&x| x != 0) {
+            Some(i) => s.coeffs[i] = -s.coeffs[i],
+            None => s.coeffs[0] = 1,
+        }
+        assert!(matches!(
+            key_set.validate(&forged),
+            Err(KeyError::Noise { .. })
+        ));
     }
 }
@@ -0,0 +1,185 @@
+use super::{
+    ciphertext::Ciphertext,
+    code::{conjugation_element, galois_element, galois_permutation, project},
+    encoder::Encoder,
+    evaluator::Evaluator,
+    plaintext::Plaintext,
+};
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::{collections::BTreeMap, fmt::Debug};
+
+// w = σ(m) ∈ ℂ^N 上の行列 M を M w = Σ_g D_g ⊙ τ_g(w) (D_g[i] = M[i][π_g(i)]) と分解する
+// g = s 5^{n1 j + i} (s = ±1) とおいて baby-step giant-step でまとめる
+// Σ_{s, j} τ_{s 5^{n1 j}}( Σ_i τ_{s 5^{n1 j}}^{-1}(D_g) ⊙ τ_{5^i}(w) )
+#[derive(Debug, Clone)]
+pub struct LinearTransform<T: Integer, const N: usize> {
+    // giant step の g -> [(baby step の g, τ^{-1}(D))]
+    steps: BTreeMap<usize, Vec<(usize, Plaintext<T, N>)>>,
+    pub scale: f64,
+}
+
+impl<T: Integer + NumCast + Default + Copy, const N: usize> LinearTransform<T, N>
+where
+    [(); N / 2]:,
+{
+    // スロット z ∈ ℂ^{N/2} への行列 A (z -> A z)
+    // 共役側には conj(A) が掛かるように σ(m) 上の行列に広げる
+    pub fn new(matrix: &[[Complex64; N / 2]], scale: f64) -> Self {
+        assert_eq!(matrix.len(), N / 2);
+
+        let mut embedded = vec![[Complex64::default(); N]; N];
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &a) in row.iter().enumerate() {
+                embedded[i][j] = a;
+                embedded[N - 1 - i][N - 1 - j] = a.conj();
+            }
+        }
+
+        Self::from_embedding(&embedded, scale)
+    }
+
+    // σ(m) ∈ ℂ^N 上の行列 (実係数の多項式を実係数に移すもの)
+    // 対角成分は scale で符号化しておく
+    pub fn from_embedding(matrix: &[[Complex64; N]], scale: f64) -> Self {
+        assert_eq!(matrix.len(), N);
+
+        // 5 の位数は N/2
+        let order = N / 2;
+        let mut n1 = 1;
+        while n1 * n1 < order {
+            n1 *= 2;
+        }
+
+        let encoder = Encoder::new(scale);
+        let mut steps: BTreeMap<usize, Vec<(usize, Plaintext<T, N>)>> = BTreeMap::new();
+        for s in [1, conjugation_element::<N>()] {
+            for k in 0..order {
+                let g = s * galois_element::<N>(k) % (2 * N);
+                let permutation = galois_permutation::<N>(g);
+                let diagonal: [Complex64; N] = std::array::from_fn(|i| matrix[i][permutation[i]]);
+                if diagonal.iter().all(|d| d.norm() < 1e-12) {
+                    continue;
+                }
+
+                let giant = s * galois_element::<N>(k - k % n1) % (2 * N);
+                let baby = galois_element::<N>(k % n1);
+                // τ_giant(y) = D となる y
+                let mut shifted = [Complex64::default(); N];
+                for (i, &j) in galois_permutation::<N>(giant).iter().enumerate() {
+                    shifted[j] = diagonal[i];
+                }
+
+                let plaintext = encoder.encode(&project(shifted)).unwrap_or_else(|e| {
+                    panic!("matrix does not map real polynomials to real: {}", e)
+                });
+                steps.entry(giant).or_default().push((baby, plaintext));
+            }
+        }
+        assert!(!steps.is_empty(), "matrix must not be zero");
+
+        Self { steps, scale }
+    }
+}
+
+impl<T: Integer, const N: usize> LinearTransform<T, N> {
+    // 必要な Galois 鍵の g (1 は除く)
+    pub fn galois_elements(&self) -> Vec<usize> {
+        let babies = self
+            .steps
+            .values()
+            .flat_map(|steps| steps.iter().map(|(baby, _)| *baby));
+        let mut elements: Vec<usize> = self.steps.keys().copied().chain(babies).collect();
+        elements.sort();
+        elements.dedup();
+        elements.retain(|&g| g != 1);
+        elements
+    }
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    // スロットに行列を掛ける (scale は transform.scale 倍になる)
+    pub fn linear_transform(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        transform: &LinearTransform<T, N>,
+    ) -> Ciphertext<T, N> {
+        let mut babies = BTreeMap::new();
+        for steps in transform.steps.values() {
+            for (baby, _) in steps {
+                babies
+                    .entry(*baby)
+                    .or_insert_with(|| self.apply_galois(ciphertext, *baby));
+            }
+        }
+
+        transform
+            .steps
+            .iter()
+            .map(|(&giant, steps)| {
+                let inner = steps
+                    .iter()
+                    .map(|(baby, plaintext)| babies[baby].clone() * *plaintext)
+                    .reduce(|acc, term| acc + term)
+                    .unwrap();
+                self.apply_galois(&inner, giant)
+            })
+            .reduce(|acc, term| acc + term)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        self,
+        testing::{Context, Z},
+    };
+
+    const N: usize = 8;
+
+    #[test]
+    fn dense_matrix() {
+        let matrix: Vec<[Complex64; N / 2]> = (0..N / 2)
+            .map(|i| {
+                std::array::from_fn(|j| {
+                    Complex64::new((i + 2 * j) as f64 / 8.0 - 0.5, 0.1 * j as f64)
+                })
+            })
+            .collect();
+        let transform = LinearTransform::<i128, N>::new(&matrix, 65536.0);
+        let context = Context::small(&transform.galois_elements());
+        let evaluator = &context.evaluator;
+
+        let result =
+            evaluator.rescale(evaluator.linear_transform(&context.encrypt(&Z), &transform));
+
+        let expected: Vec<Complex64> = matrix
+            .iter()
+            .map(|row| row.iter().zip(&Z).map(|(a, z)| a * z).sum())
+            .collect();
+        let bits = ckks::precision_bits(&expected, &context.decrypt(&result));
+        assert!(bits > 8.0);
+    }
+
+    #[test]
+    fn diagonal_matrix_needs_no_keys() {
+        let d = [1.0, -2.0, 0.5, 3.0];
+        let matrix: Vec<[Complex64; N / 2]> = (0..N / 2)
+            .map(|i| std::array::from_fn(|j| Complex64::new(if i == j { d[i] } else { 0.0 }, 0.0)))
+            .collect();
+        let transform = LinearTransform::<i128, N>::new(&matrix, 65536.0);
+        assert!(transform.galois_elements().is_empty());
+
+        let context = Context::small(&[]);
+        let evaluator = &context.evaluator;
+        let z = [Complex64::new(1.0, 1.0); N / 2];
+        let result =
+            evaluator.rescale(evaluator.linear_transform(&context.encrypt(&z), &transform));
+
+        let expected = d.map(|d| Complex64::new(d, d));
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&result)) > 8.0);
+    }
+}
@@ -1,4 +1,5 @@
 use num_integer::Integer;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
 // Remainder in range (-modulo/2, modulo/2]
 pub fn cmod<T: Integer + Copy>(x: T, modulo: T) -> T {
@@ -6,8 +7,6 @@ pub fn cmod<T: Integer + Copy>(x: T, modulo: T) -> T {
         return x;
     }
 
-    println!("MODULO OVER!!");
-
     let t = x % modulo;
 
     let two = T::one() + T::one();
@@ -16,6 +15,54 @@ pub fn cmod<T: Integer + Copy>(x: T, modulo: T) -> T {
     if t < half_modulo { t } else { t - modulo }
 }
 
+// 秘密鍵や暗号化の乱数に依存する値用
+// cmod と同じ (-modulo/2, modulo/2] の剰余を、値で分岐せずに求める (0 < modulo <= 2^{BITS-2})
+pub trait ConstantTime: Integer + Copy {
+    fn cmod_ct(self, modulo: Self) -> Self;
+}
+
+pub fn cmod_ct<T: ConstantTime>(x: T, modulo: T) -> T {
+    x.cmod_ct(modulo)
+}
+
+macro_rules! impl_constant_time {
+    ($($signed:ty => $unsigned:ty),*) => {$(
+        impl ConstantTime for $signed {
+            fn cmod_ct(self, modulo: Self) -> Self {
+                const BITS: u32 = <$signed>::BITS;
+                assert!(modulo > 0 && modulo <= 1 << (BITS - 2));
+
+                // |x| (MIN も 2^{BITS-1} になる)
+                let negative = Choice::from((self >> (BITS - 1) & 1) as u8);
+                let magnitude =
+                    <$signed>::conditional_select(&self, &self.wrapping_neg(), negative) as $unsigned;
+
+                // 上の桁から 1 ビットずつ下ろして r >= m なら引く (常に BITS 回)
+                // r < 2m <= 2^{BITS-1} なので r - m の最上位ビットが r < m を表す
+                let m = modulo as $unsigned;
+                let mut r: $unsigned = 0;
+                for i in (0..BITS).rev() {
+                    r = r << 1 | (magnitude >> i & 1);
+                    let d = r.wrapping_sub(m);
+                    let borrow = Choice::from((d >> (BITS - 1)) as u8);
+                    r = <$unsigned>::conditional_select(&d, &r, borrow);
+                }
+
+                // |x| mod m -> x mod m ∈ [0, m)
+                let r = r as $signed;
+                let flip = negative & !r.ct_eq(&0);
+                let r = <$signed>::conditional_select(&r, &(modulo - r), flip);
+
+                // r > m/2 なら r - m
+                let over = Choice::from(((modulo >> 1).wrapping_sub(r) >> (BITS - 1) & 1) as u8);
+                <$signed>::conditional_select(&r, &(r - modulo), over)
+            }
+        }
+    )*};
+}
+
+impl_constant_time!(i64 => u64, i128 => u128);
+
 pub fn is_in_range<T: Integer + Copy>(x: T, modulo: T) -> bool {
     let zero = T::zero();
     let two = T::one() + T::one();
@@ -24,3 +71,114 @@ pub fn is_in_range<T: Integer + Copy>(x: T, modulo: T) -> bool {
     // Check if x is in the range (-modulo/2, modulo/2]
     x + half_modulo > zero && x - half_modulo <= zero
 }
+
+// round(x / d) (d > 0)
+pub fn round_div<T: Integer + Copy>(x: T, d: T) -> T {
+    let two = T::one() + T::one();
+
+    (x * two + d).div_floor(&(d * two))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::testing::{Context, timing_t};
+    use num_complex::Complex64;
+
+    // x mod m を (-m/2, m/2] に
+    fn centered<T: Integer + Copy>(x: T, modulo: T) -> T {
+        let r = x.mod_floor(&modulo);
+        if r > modulo / (T::one() + T::one()) {
+            r - modulo
+        } else {
+            r
+        }
+    }
+
+    #[test]
+    fn cmod_ct() {
+        for modulo in [2, 5, 1000, 1 << 40, (1 << 62) - 1, 1 << 62] {
+            let xs = [
+                0,
+                1,
+                -1,
+                modulo / 2,
+                -modulo / 2,
+                modulo,
+                -modulo,
+                i64::MAX,
+                i64::MIN,
+            ];
+            for x in xs.into_iter().chain((0..1000).map(|_| rand::random())) {
+                assert_eq!(
+                    super::cmod_ct(x, modulo),
+                    centered(x, modulo),
+                    "{} mod {}",
+                    x,
+                    modulo
+                );
+            }
+        }
+
+        for modulo in [3, 1i128 << 100, (1 << 126) - 1] {
+            for x in [i128::MIN, i128::MAX]
+                .into_iter()
+                .chain((0..1000).map(|_| rand::random()))
+            {
+                assert_eq!(super::cmod_ct(x, modulo), centered(x, modulo));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn cmod_ct_rejects_large_modulus() {
+        super::cmod_ct(0i64, (1 << 62) + 1);
+    }
+
+    // cargo test --release -- --ignored timing
+    #[test]
+    #[ignore]
+    fn timing_detects_branching_cmod() {
+        let modulo = 1i128 << 80;
+        let t = timing_t(100_000, || 1i128, rand::random::<i128>, |x| cmod(x, modulo));
+        assert!(t.abs() > 4.5, "t = {}", t);
+    }
+
+    #[test]
+    #[ignore]
+    fn timing_cmod_ct() {
+        let modulo = 1i128 << 80;
+        let t = timing_t(
+            100_000,
+            || 1i128,
+            rand::random::<i128>,
+            |x| super::cmod_ct(x, modulo),
+        );
+        assert!(t.abs() < 4.5, "t = {}", t);
+    }
+
+    #[test]
+    #[ignore]
+    fn timing_decrypt() {
+        const N: usize = 8;
+        let context = Context::<N>::small(&[]);
+        let z = [Complex64::new(0.5, -0.25); N / 2];
+        let ciphertext = context.encrypt(&z);
+
+        // 同じ暗号文を固定の秘密鍵とランダムな秘密鍵で復号する
+        let ql = ciphertext.modulo();
+        let random_key = || {
+            let mut secret_key = crate::ckks::keys::SecretKey::<i128, N>::generate(ql);
+            secret_key.id = ciphertext.key_id;
+            secret_key
+        };
+        let t = timing_t(
+            20_000,
+            || context.secret_key.clone(),
+            random_key,
+            |secret_key| crate::ckks::decrypt(ciphertext.clone(), &secret_key),
+        );
+        assert!(t.abs() < 4.5, "t = {}", t);
+    }
+}
@@ -0,0 +1,159 @@
+use super::{approx, ciphertext::Ciphertext, evaluator::Evaluator};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::fmt::Debug;
+
+// inverse が消費するレベル数
+pub fn inverse_depth(iterations: u32) -> u32 {
+    if iterations == 0 { 1 } else { iterations + 2 }
+}
+
+// inv_sqrt が消費するレベル数
+pub fn inv_sqrt_depth(iterations: u32) -> u32 {
+    1 + 2 * iterations
+}
+
+// sqrt が消費するレベル数
+pub fn sqrt_depth(iterations: u32) -> u32 {
+    inv_sqrt_depth(iterations) + 1
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    // 1/x (x ∈ [a, b], 0 < a)
+    // Goldschmidt: c = 2/(a+b), e = 1 - c x とおくと
+    // 1/x = c/(1 - e) = c (1 + e)(1 + e^2)(1 + e^4)...
+    // 相対誤差は ((b-a)/(b+a))^{2^{iterations+1}}
+    pub fn inverse(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        range: (f64, f64),
+        iterations: u32,
+    ) -> Ciphertext<T, N> {
+        let (a, b) = range;
+        assert!(0.0 < a && a < b, "invalid range [{}, {}]", a, b);
+
+        let x = self.match_scale(ciphertext.clone());
+        let c = 2.0 / (a + b);
+        let mut e = self.add_const(self.mul_const(x.clone(), -c), 1.0);
+        // c (1 + e) = 2c - c^2 x
+        let mut y = self.add_const(self.mul_const(x, -c * c), 2.0 * c);
+        for _ in 0..iterations {
+            e = self.rescale(e.clone() * e);
+            y = self.rescale(y * self.add_const(e.clone(), 1.0));
+        }
+
+        y
+    }
+
+    // 1/√x (x ∈ [a, b], 0 < a)
+    // 初期値は [a, b] 上の最良1次近似で、Newton 法 y <- y (3 - x y^2) / 2 を繰り返す
+    pub fn inv_sqrt(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        range: (f64, f64),
+        iterations: u32,
+    ) -> Ciphertext<T, N> {
+        let (a, b) = range;
+        assert!(0.0 < a && a < b, "invalid range [{}, {}]", a, b);
+
+        let x = self.match_scale(ciphertext.clone());
+        // c0 + c1 t (t = (2x - a - b)/(b - a)) を x の1次式に直す
+        let approximation = approx::remez(|x| 1.0 / x.sqrt(), range, 1);
+        let coeffs = &approximation.polynomial.coeffs;
+        let slope = 2.0 * coeffs[1] / (b - a);
+        let intercept = coeffs[0] - coeffs[1] * (a + b) / (b - a);
+        let mut y = self.add_const(self.mul_const(x.clone(), slope), intercept);
+
+        let half = self.mul_const(x, 0.5);
+        for _ in 0..iterations {
+            // 1.5 y - (x/2 * y) * y^2
+            let square = self.rescale(y.clone() * y.clone());
+            let product = self.rescale(half.clone() * y.clone());
+            let cube = self.rescale(product * square);
+            y = self.sub(&self.mul_const(y, 1.5), &cube);
+        }
+
+        y
+    }
+
+    // √x = x * (1/√x)
+    pub fn sqrt(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        range: (f64, f64),
+        iterations: u32,
+    ) -> Ciphertext<T, N> {
+        let x = self.match_scale(ciphertext.clone());
+        let y = self.inv_sqrt(&x, range, iterations);
+
+        self.rescale(x * y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{self, params::Params, testing::Context};
+    use num_complex::Complex64;
+
+    const N: usize = 8;
+    const RANGE: (f64, f64) = (1.0, 4.0);
+    const X: [f64; 4] = [1.0, 1.7, 2.5, 3.9];
+
+    fn context() -> Context<N> {
+        let params = Params::<i128>::new(6, 1 << 14, 1 << 20, 1 << 16);
+        Context::new(params, &[])
+    }
+
+    // rescale ごとに scale がずれる法の列
+    fn context_with_factors() -> Context<N> {
+        let factors = vec![16381, 16411, 16369, 16417, 16363, 16421];
+        let params = Params::<i128>::new(6, 1 << 14, 1 << 20, 1 << 16).with_factors(factors);
+        Context::new(params, &[])
+    }
+
+    fn check(
+        context: &Context<N>,
+        result: &Ciphertext<i128, N>,
+        f: fn(f64) -> f64,
+        depth: u32,
+    ) -> f64 {
+        let evaluator = &context.evaluator;
+        assert_eq!(evaluator.params.limit - evaluator.level(result), depth);
+
+        let expected = X.map(|x| Complex64::new(f(x), 0.0));
+        ckks::precision_bits(&expected, &context.decrypt(result))
+    }
+
+    #[test]
+    fn inverse() {
+        for context in [context(), context_with_factors()] {
+            let ciphertext = context.encrypt(&X.map(|x| Complex64::new(x, 0.0)));
+            let result = context.evaluator.inverse(&ciphertext, RANGE, 3);
+
+            let bits = check(&context, &result, |x| 1.0 / x, inverse_depth(3));
+            assert!(bits > 7.0);
+        }
+    }
+
+    #[test]
+    fn inv_sqrt() {
+        for context in [context(), context_with_factors()] {
+            let ciphertext = context.encrypt(&X.map(|x| Complex64::new(x, 0.0)));
+            let result = context.evaluator.inv_sqrt(&ciphertext, RANGE, 2);
+
+            let bits = check(&context, &result, |x| 1.0 / x.sqrt(), inv_sqrt_depth(2));
+            assert!(bits > 7.0);
+        }
+    }
+
+    #[test]
+    fn sqrt() {
+        let context = context();
+        let ciphertext = context.encrypt(&X.map(|x| Complex64::new(x, 0.0)));
+        let result = context.evaluator.sqrt(&ciphertext, RANGE, 2);
+
+        let bits = check(&context, &result, f64::sqrt, sqrt_depth(2));
+        assert!(bits > 7.0);
+    }
+}
@@ -0,0 +1,120 @@
+use num_integer::Integer;
+use num_traits::NumCast;
+
+use super::poly::Ring;
+
+// 法の列 q_l = q0 * f_1 * ... * f_l (0 <= l <= limit)
+// 鍵切り替えは P*q_limit で行う
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Params<T> {
+    pub limit: u32,
+    // 名目上の scale Δ
+    pub p: T,
+    pub q0: T,
+    // 鍵切り替え用の P
+    pub scale: T,
+    // rescale で割る数 (q_l -> q_{l-1} は factors[l-1] で割る)
+    // 既定はすべて p
+    pub factors: Vec<T>,
+    // true なら各レベルの scale を Δ_limit = f_limit, Δ_{l-1} = Δ_l^2 / f_l と決める
+    // 同じレベルの暗号文どうしを掛けて rescale するとちょうど次のレベルの scale になる
+    pub exact_scales: bool,
+    // 鍵と暗号文の環 (ConjugateInvariant なら encode_real の平文だけを暗号化する)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ring: Ring,
+}
+
+impl<T: Integer + Copy> Params<T> {
+    pub fn new(limit: u32, p: T, q0: T, scale: T) -> Self {
+        Self {
+            limit,
+            p,
+            q0,
+            scale,
+            factors: vec![p; limit as usize],
+            exact_scales: false,
+            ring: Ring::Negacyclic,
+        }
+    }
+
+    // Δ の近くの別々の数で rescale する法の列
+    pub fn with_factors(self, factors: Vec<T>) -> Self {
+        assert_eq!(
+            factors.len(),
+            self.limit as usize,
+            "need one factor per level"
+        );
+        Self { factors, ..self }
+    }
+
+    pub fn with_exact_scales(self) -> Self {
+        Self {
+            exact_scales: true,
+            ..self
+        }
+    }
+
+    // 実数スロットだけを使うなら Ring::ConjugateInvariant (スロットが N 個になる)
+    pub fn with_ring(self, ring: Ring) -> Self {
+        Self { ring, ..self }
+    }
+
+    // q_l
+    pub fn modulo(&self, level: u32) -> T {
+        self.factors[..level as usize]
+            .iter()
+            .fold(self.q0, |q, &f| q * f)
+    }
+
+    // q_l -> l
+    pub fn level(&self, modulo: T) -> u32 {
+        self.try_level(modulo)
+            .expect("modulo is not in the modulus chain")
+    }
+
+    // 法の列に入っていなければ None
+    pub fn try_level(&self, modulo: T) -> Option<u32> {
+        (0..=self.limit).find(|&l| self.modulo(l) == modulo)
+    }
+
+    // レベル l の暗号文を rescale するときに割る数 f_l
+    pub fn factor(&self, level: u32) -> T {
+        assert!(level > 0, "cannot rescale at level 0");
+        self.factors[level as usize - 1]
+    }
+}
+
+impl<T: Integer + Copy + NumCast> Params<T> {
+    // limit, p, q0, scale, factors, exact_scales, ring の FNV-1a (保存した鍵や暗号文と突き合わせる)
+    // Negacyclic なら ring は入れない (以前の fingerprint と変わらない)
+    pub fn fingerprint(&self) -> u64 {
+        let mut bytes = self.limit.to_le_bytes().to_vec();
+        bytes.push(self.exact_scales as u8);
+        if self.ring == Ring::ConjugateInvariant {
+            bytes.push(1);
+        }
+        for x in [self.p, self.q0, self.scale].iter().chain(&self.factors) {
+            bytes.extend(x.to_i128().unwrap().to_le_bytes());
+        }
+        fnv1a(&bytes)
+    }
+
+    // レベル l で符号化するときの scale
+    pub fn level_scale(&self, level: u32) -> f64 {
+        if !self.exact_scales {
+            return self.p.to_f64().unwrap();
+        }
+
+        let top = self.factor(self.limit).to_f64().unwrap();
+        (level + 1..=self.limit).rev().fold(top, |scale, l| {
+            scale * scale / self.factor(l).to_f64().unwrap()
+        })
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
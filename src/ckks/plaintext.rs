@@ -2,17 +2,108 @@ use std::ops::{Add, Mul};
 
 use num_integer::Integer;
 
-use super::poly::Poly;
+use super::{
+    modulo::cmod,
+    params::Params,
+    poly::{Poly, Ring},
+};
+
+// m が何を表しているか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    // σ(m) のスロットに値を詰める (encode)
+    Slots,
+    // m の係数そのもの (encode_coeffs)
+    Coefficients,
+    // 実数のスロット (encode_real)
+    // m は共役不変環 ℤ[X + X^{-1}] ⊂ ℤ[X]/(X^{2N} + 1) の元で、N 個のスロットがすべて実数になる
+    Real,
+}
+
+impl Encoding {
+    // m の属する環
+    pub fn ring(self) -> Ring {
+        match self {
+            Self::Slots | Self::Coefficients => Ring::Negacyclic,
+            Self::Real => Ring::ConjugateInvariant,
+        }
+    }
+
+    // 符号化の違うもの同士は演算できない
+    pub(crate) fn combine(self, rhs: Self) -> Self {
+        assert_eq!(
+            self, rhs,
+            "cannot combine {:?} and {:?} encodings",
+            self, rhs
+        );
+        self
+    }
+}
+
+// 相対誤差がこれ以下の scale は等しいとみなす
+pub const SCALE_TOLERANCE: f64 = 1e-6;
+
+pub fn scales_match(a: f64, b: f64) -> bool {
+    (a - b).abs() <= SCALE_TOLERANCE * a.abs().max(b.abs())
+}
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Serialize",
+        deserialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Deserialize<'de>"
+    ))
+)]
 pub struct Plaintext<T: Integer, const N: usize> {
     pub m: Poly<T, N>,
-    pub scale: T,
+    // 値 = m / scale (rescale で p 以外の数で割ることもあるので実数で持つ)
+    pub scale: f64,
+    // 使っているスロット数 n (n | N/2、Real なら n | N)
+    // n がそれより小さいときスロットは周期 n で繰り返されている
+    pub slots: usize,
+    pub encoding: Encoding,
+    // 法 q_l で取り直した (または法を決めて符号化した) なら Some(q_l)
+    pub modulo: Option<T>,
 }
 
 impl<T: Integer, const N: usize> Plaintext<T, N> {
-    pub fn new(m: Poly<T, N>, scale: T) -> Self {
-        Self { m, scale }
+    pub fn new(m: Poly<T, N>, scale: f64) -> Self {
+        Self {
+            m,
+            scale,
+            slots: N / 2,
+            encoding: Encoding::Slots,
+            modulo: None,
+        }
+    }
+}
+
+impl<T: Integer + Copy, const N: usize> Plaintext<T, N> {
+    // 係数を q_level で取り直す (レベル level の暗号文と組み合わせる)
+    pub fn mod_switch_to(self, params: &Params<T>, level: u32) -> Self {
+        let modulo = params.modulo(level);
+
+        Self {
+            m: Poly::new(self.m.coeffs.map(|c| cmod(c, modulo))),
+            modulo: Some(modulo),
+            ..self
+        }
+    }
+
+    // q_l -> q_{l-1} (scale はそのまま)
+    pub fn mod_switch_to_next(self, params: &Params<T>) -> Self {
+        let modulo = self
+            .modulo
+            .expect("plaintext has no modulus, use mod_switch_to");
+        let current = params
+            .try_level(modulo)
+            .expect("modulo is not in the modulus chain");
+        assert!(current > 0, "cannot switch below level 0");
+
+        self.mod_switch_to(params, current - 1)
     }
 }
 
@@ -20,9 +111,19 @@ impl<T: Integer + Copy + Default, const N: usize> Add for Plaintext<T, N> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self {
+        assert!(
+            scales_match(self.scale, rhs.scale),
+            "scales {} and {} differ",
+            self.scale,
+            rhs.scale
+        );
+
         Self {
             m: self.m + rhs.m,
             scale: self.scale,
+            slots: self.slots.max(rhs.slots),
+            encoding: self.encoding.combine(rhs.encoding),
+            modulo: self.modulo.or(rhs.modulo),
         }
     }
 }
@@ -31,9 +132,14 @@ impl<T: Integer + Copy + Default, const N: usize> Mul for Plaintext<T, N> {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self {
+        let encoding = self.encoding.combine(rhs.encoding);
+
         Self {
-            m: self.m * rhs.m,
+            m: self.m.mul_in(rhs.m, encoding.ring()),
             scale: self.scale * rhs.scale,
+            slots: self.slots.max(rhs.slots),
+            encoding,
+            modulo: self.modulo.or(rhs.modulo),
         }
     }
 }
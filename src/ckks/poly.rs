@@ -1,4 +1,4 @@
-use super::modulo::cmod;
+use super::modulo::{ConstantTime, cmod, cmod_ct};
 use cauchy::Scalar;
 use num_integer::Integer;
 use rand::distr::uniform::SampleUniform;
@@ -6,6 +6,66 @@ use std::{
     fmt::Debug,
     ops::{Add, Div, Mul, Neg, Range, Sub},
 };
+use zeroize::Zeroize;
+
+// 係数がどの環の元を表しているか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ring {
+    // ℤ[X]/(X^N + 1) (係数は X^j の係数)
+    #[default]
+    Negacyclic,
+    // 共役不変な部分環 ℤ[X + X^{-1}] ⊂ ℤ[X]/(X^{2N} + 1)
+    // 係数は基底 1, X^j + X^{-j} (1 <= j < N) についての係数
+    ConjugateInvariant,
+}
+
+impl Ring {
+    // X -> X^g の g はこの数を法として考える
+    pub fn galois_order<const N: usize>(self) -> usize {
+        match self {
+            Self::Negacyclic => 2 * N,
+            Self::ConjugateInvariant => 4 * N,
+        }
+    }
+}
+
+// 共役不変環での積 (add, mul は係数の演算)
+// T_k = X^k + X^{-k} とすると T_i T_j = T_{i+j} + T_{|i-j|}
+// T_0 = 2, T_N = 0, T_{2N-k} = -T_k なので足し込む先と符号は添字だけで決まる
+fn conjugate_invariant_mul<T: Copy, const N: usize>(
+    a: &[T; N],
+    b: &[T; N],
+    zero: T,
+    add: impl Fn(T, T) -> T,
+    sub: impl Fn(T, T) -> T,
+    mul: impl Fn(T, T) -> T,
+) -> [T; N] {
+    let mut c = [zero; N];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            let xy = mul(x, y);
+            if i == 0 || j == 0 {
+                c[i + j] = add(c[i + j], xy);
+                continue;
+            }
+
+            let k = i + j;
+            if k < N {
+                c[k] = add(c[k], xy);
+            } else if k > N {
+                c[2 * N - k] = sub(c[2 * N - k], xy);
+            }
+            let d = i.abs_diff(j);
+            c[d] = add(c[d], xy);
+            if d == 0 {
+                c[0] = add(c[0], xy);
+            }
+        }
+    }
+
+    c
+}
 
 #[derive(Debug, Clone, Copy)]
 // Polynomial expression on ℤ[X]/(X^N + 1)
@@ -40,6 +100,24 @@ impl<T, const N: usize> Poly<T, N> {
 
         result
     }
+
+    // ring での積 (Negacyclic なら * と同じ)
+    pub fn mul_in(self, rhs: Self, ring: Ring) -> Self
+    where
+        T: Mul<Output = T> + Add<Output = T> + Sub<Output = T> + Copy + Default,
+    {
+        match ring {
+            Ring::Negacyclic => self * rhs,
+            Ring::ConjugateInvariant => Self::new(conjugate_invariant_mul(
+                &self.coeffs,
+                &rhs.coeffs,
+                T::default(),
+                |x, y| x + y,
+                |x, y| x - y,
+                |x, y| x * y,
+            )),
+        }
+    }
 }
 
 impl<T: Neg<Output = T>, const N: usize> Neg for Poly<T, N> {
@@ -95,7 +173,7 @@ impl<T: Mul<Output = T> + Add<Output = T> + Sub<Output = T> + Copy + Default, co
         for i in 0..(N - 1) {
             new_coeffs[i] = product[i] - product[i + N];
         }
-        
+
         new_coeffs[N - 1] = product[N - 1];
 
         Self::new(new_coeffs)
@@ -130,6 +208,13 @@ pub struct ModPoly<T: Integer, const N: usize> {
     pub modulo: T,
 }
 
+// 秘密鍵から作った多項式を Zeroizing で包めるように
+impl<T: Integer + Zeroize, const N: usize> Zeroize for ModPoly<T, N> {
+    fn zeroize(&mut self) {
+        self.coeffs.zeroize();
+    }
+}
+
 impl<T: Integer + Copy, const N: usize> ModPoly<T, N> {
     pub fn new(coeffs: [T; N], modulo: T) -> Self {
         Self { coeffs, modulo }
@@ -144,12 +229,166 @@ impl<T: Integer + Copy, const N: usize> ModPoly<T, N> {
         Self { coeffs, modulo }
     }
 
-    pub fn with_modulo(self, modulo: T) -> Self {
+    // 秘密の係数 (s, e, v) 用に [range.start, range.end) から棄却せずに取る
+    // (r * width) >> 64 の偏りは width/2^64 以下
+    pub fn new_random_ct(range: Range<i64>, modulo: T) -> Self
+    where
+        T: From<i64>,
+    {
+        let width = range.end.abs_diff(range.start) as u128;
+        let coeffs: [T; N] = std::array::from_fn(|_| {
+            let r = rand::random::<u64>() as u128;
+            T::from(range.start + ((r * width) >> 64) as i64)
+        });
+
+        Self { coeffs, modulo }
+    }
+
+    // 秘密鍵にも使うので借りて作る
+    pub fn with_modulo(&self, modulo: T) -> Self {
         Self {
             coeffs: self.coeffs,
             modulo,
         }
     }
+
+    // 係数を modulo で取り直す
+    pub fn reduce(self, modulo: T) -> Self {
+        Self {
+            coeffs: self.coeffs.map(|c| cmod(c, modulo)),
+            modulo,
+        }
+    }
+
+    // τ_g: P(X) -> P(X^g) (g は奇数)
+    // X^N = -1 より X^{jg} = ±X^{jg mod N}
+    pub fn automorphism(self, g: usize) -> Self {
+        self.automorphism_with(g, Ring::Negacyclic, cmod)
+    }
+
+    // 共役不変環では X^j + X^{-j} -> X^{jg} + X^{-jg} (g は 4N を法とする奇数)
+    pub fn automorphism_in(self, g: usize, ring: Ring) -> Self {
+        self.automorphism_with(g, ring, cmod)
+    }
+
+    // ring での積 (Negacyclic なら * と同じ)
+    pub fn mul_in(self, rhs: Self, ring: Ring) -> Self {
+        self.mul_with(&rhs, ring, cmod)
+    }
+
+    // 分岐は添字 j と g だけで決まる
+    fn automorphism_with(&self, g: usize, ring: Ring, reduce: fn(T, T) -> T) -> Self {
+        if ring == Ring::ConjugateInvariant {
+            return self.conjugate_invariant_automorphism(g, reduce);
+        }
+
+        let mut new_coeffs: [T; N] = [T::zero(); N];
+        for (j, c) in self.coeffs.iter().enumerate() {
+            let k = j * g % (2 * N);
+            if k < N {
+                new_coeffs[k] = *c;
+            } else {
+                new_coeffs[k - N] = reduce(T::zero() - *c, self.modulo);
+            }
+        }
+
+        Self::new(new_coeffs, self.modulo)
+    }
+
+    // T_j -> T_{jg} で、r = jg mod 4N は T_r = T_{4N-r}、T_r = -T_{2N-r} (N < r < 2N) で戻す
+    // j < N なので r が 0 や N になることはない
+    fn conjugate_invariant_automorphism(&self, g: usize, reduce: fn(T, T) -> T) -> Self {
+        let mut new_coeffs: [T; N] = [T::zero(); N];
+        new_coeffs[0] = self.coeffs[0];
+        for (j, c) in self.coeffs.iter().enumerate().skip(1) {
+            let r = j * g % (4 * N);
+            let r = r.min(4 * N - r);
+            if r < N {
+                new_coeffs[r] = *c;
+            } else {
+                new_coeffs[2 * N - r] = reduce(T::zero() - *c, self.modulo);
+            }
+        }
+
+        Self::new(new_coeffs, self.modulo)
+    }
+
+    fn mul_with(&self, rhs: &Self, ring: Ring, reduce: fn(T, T) -> T) -> Self {
+        let modulo = self.modulo;
+        if ring == Ring::ConjugateInvariant {
+            let coeffs = conjugate_invariant_mul(
+                &self.coeffs,
+                &rhs.coeffs,
+                T::zero(),
+                |x, y| reduce(x + y, modulo),
+                |x, y| reduce(x - y, modulo),
+                |x, y| reduce(x * y, modulo),
+            );
+            return Self::new(coeffs, modulo);
+        }
+
+        let mut product = vec![T::zero(); 2 * N - 1];
+
+        for (i, a) in self.coeffs.iter().enumerate() {
+            for (j, b) in rhs.coeffs.iter().enumerate() {
+                product[i + j] = reduce(product[i + j] + reduce(*a * *b, self.modulo), self.modulo);
+            }
+        }
+        let mut new_coeffs: [T; N] = [T::zero(); N];
+        for i in 0..(N - 1) {
+            new_coeffs[i] = reduce(product[i] - product[i + N], self.modulo);
+        }
+
+        new_coeffs[N - 1] = reduce(product[N - 1], self.modulo);
+
+        Self::new(new_coeffs, self.modulo)
+    }
+}
+
+// 秘密鍵や暗号化の乱数に依存する多項式用
+// 演算子の代わりに使い、cmod の代わりに分岐しない cmod_ct で取り直す
+// 秘密鍵を複製しないように引数は借りる
+impl<T: ConstantTime, const N: usize> ModPoly<T, N> {
+    pub fn reduce_ct(self, modulo: T) -> Self {
+        Self {
+            coeffs: self.coeffs.map(|c| cmod_ct(c, modulo)),
+            modulo,
+        }
+    }
+
+    pub fn neg_ct(self) -> Self {
+        let new_coeffs: [T; N] = self.coeffs.map(|c| cmod_ct(T::zero() - c, self.modulo));
+
+        Self::new(new_coeffs, self.modulo)
+    }
+
+    pub fn add_ct(&self, rhs: &Self) -> Self {
+        let new_coeffs: [T; N] =
+            std::array::from_fn(|i| cmod_ct(self.coeffs[i] + rhs.coeffs[i], self.modulo));
+
+        Self::new(new_coeffs, self.modulo)
+    }
+
+    pub fn sub_ct(&self, rhs: &Self) -> Self {
+        let new_coeffs: [T; N] =
+            std::array::from_fn(|i| cmod_ct(self.coeffs[i] - rhs.coeffs[i], self.modulo));
+
+        Self::new(new_coeffs, self.modulo)
+    }
+
+    pub fn mul_ct(&self, rhs: &Self, ring: Ring) -> Self {
+        self.mul_with(rhs, ring, cmod_ct)
+    }
+
+    pub fn mul_scalar_ct(&self, rhs: T) -> Self {
+        let new_coeffs: [T; N] = self.coeffs.map(|c| cmod_ct(c * rhs, self.modulo));
+
+        Self::new(new_coeffs, self.modulo)
+    }
+
+    pub fn automorphism_ct(&self, g: usize, ring: Ring) -> Self {
+        self.automorphism_with(g, ring, cmod_ct)
+    }
 }
 
 impl<T: Integer + Neg<Output = T> + Copy, const N: usize> Neg for ModPoly<T, N> {
@@ -192,21 +431,7 @@ impl<T: Integer + Copy, const N: usize> Mul for ModPoly<T, N> {
     type Output = Self;
 
     fn mul(self, rhs: ModPoly<T, N>) -> Self::Output {
-        let mut product = vec![T::zero(); 2 * N - 1];
-
-        for (i, a) in self.coeffs.iter().enumerate() {
-            for (j, b) in rhs.coeffs.iter().enumerate() {
-                product[i + j] = cmod(product[i + j] + cmod(*a * *b, self.modulo), self.modulo);
-            }
-        }
-        let mut new_coeffs: [T; N] = [T::zero(); N];
-        for i in 0..(N - 1) {
-            new_coeffs[i] = cmod(product[i] - product[i + N], self.modulo);
-        }
-
-        new_coeffs[N - 1] = cmod(product[N - 1], self.modulo);
-
-        Self::new(new_coeffs, self.modulo)
+        self.mul_with(&rhs, Ring::Negacyclic, cmod)
     }
 }
 
@@ -279,6 +504,94 @@ mod tests {
         let poly = ModPoly::<i64, 4>::new([1, 2, 3, 4], 5);
         let scalar = 2;
         let prod_poly = poly * scalar;
-        assert_eq!(prod_poly.coeffs, [-3, -1, 1, -2]);
+        assert_eq!(prod_poly.coeffs, [2, -1, 1, -2]);
+    }
+
+    #[test]
+    fn automorphism() {
+        use super::*;
+
+        // X -> X^3 on ℤ[X]/(X^4 + 1)
+        // 1 + 2X + 3X^2 + 4X^3 -> 1 + 2X^3 + 3X^6 + 4X^9 = 1 + 4X - 3X^2 + 2X^3
+        let poly = ModPoly::<i64, 4>::new([1, 2, 3, 4], 11);
+        assert_eq!(poly.automorphism(3).coeffs, [1, 4, -3, 2]);
+    }
+
+    #[test]
+    fn conjugate_invariant() {
+        use super::*;
+
+        // a_0 + Σ a_j (X^j + X^{-j}) を ℤ[X]/(X^8 + 1) の元として計算したものと一致する
+        // X^{-j} = -X^{8-j}
+        let lift = |p: ModPoly<i64, 4>| {
+            let mut coeffs = [0; 8];
+            coeffs[0] = p.coeffs[0];
+            for j in 1..4 {
+                coeffs[j] += p.coeffs[j];
+                coeffs[8 - j] -= p.coeffs[j];
+            }
+            coeffs.map(|c: i64| c.mod_floor(&p.modulo))
+        };
+        let lift8 = |p: ModPoly<i64, 8>| p.coeffs.map(|c| c.mod_floor(&p.modulo));
+
+        let q = 1009;
+        let ring = Ring::ConjugateInvariant;
+        let a = ModPoly::<i64, 4>::new_random(-504..505, q);
+        let b = ModPoly::<i64, 4>::new_random(-504..505, q);
+        let a8 = ModPoly::<i64, 8>::new(lift(a), q);
+        let b8 = ModPoly::<i64, 8>::new(lift(b), q);
+        assert_eq!(lift(a.mul_in(b, ring)), lift8(a8 * b8));
+        for g in [3, 5, 7, 9, 15] {
+            assert_eq!(lift(a.automorphism_in(g, ring)), lift8(a8.automorphism(g)));
+        }
+
+        let c = Poly::<i64, 4>::new(a.coeffs).mul_in(Poly::new(b.coeffs), ring);
+        assert_eq!(lift(ModPoly::new(c.coeffs, q)), lift(a.mul_in(b, ring)));
+    }
+
+    #[test]
+    fn constant_time_ops() {
+        use super::*;
+
+        // 演算子と同じ剰余類で、(-q/2, q/2] に収まる
+        let q = 1009;
+        let a = ModPoly::<i64, 4>::new_random(-504..505, q);
+        let b = ModPoly::<i64, 4>::new_random(-504..505, q);
+        let same = |x: ModPoly<i64, 4>, y: ModPoly<i64, 4>| {
+            let x = x.coeffs.map(|c| c.mod_floor(&q));
+            let y = y.coeffs.map(|c| c.mod_floor(&q));
+            assert_eq!(x, y);
+        };
+        same(a.neg_ct(), -a);
+        same(a.add_ct(&b), a + b);
+        same(a.sub_ct(&b), a - b);
+        same(a.mul_ct(&b, Ring::Negacyclic), a * b);
+        same(
+            a.mul_ct(&b, Ring::ConjugateInvariant),
+            a.mul_in(b, Ring::ConjugateInvariant),
+        );
+        same(a.mul_scalar_ct(7), a * 7);
+        same(a.automorphism_ct(3, Ring::Negacyclic), a.automorphism(3));
+        same(
+            a.automorphism_ct(5, Ring::ConjugateInvariant),
+            a.automorphism_in(5, Ring::ConjugateInvariant),
+        );
+        assert_eq!(
+            ModPoly::<i64, 4>::new([1, 2, 3, 4], 5)
+                .mul_ct(&ModPoly::new([4, 3, 2, 1], 5), Ring::Negacyclic)
+                .coeffs,
+            [-1, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn new_random_ct() {
+        use super::*;
+
+        let poly = ModPoly::<i64, 1024>::new_random_ct(-3..3, 1000);
+        assert!(poly.coeffs.iter().all(|c| (-3..3).contains(c)));
+        for value in -3..3 {
+            assert!(poly.coeffs.contains(&value));
+        }
     }
 }
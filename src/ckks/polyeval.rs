@@ -0,0 +1,372 @@
+use super::{
+    ciphertext::Ciphertext,
+    evaluator::{Evaluator, constant},
+    plaintext::scales_match,
+};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Basis {
+    // Σ c_i x^i
+    Monomial,
+    // Σ c_i T_i(x)
+    Chebyshev,
+}
+
+#[derive(Debug, Clone)]
+pub struct Polynomial {
+    pub coeffs: Vec<f64>,
+    pub basis: Basis,
+}
+
+impl Polynomial {
+    pub fn new(coeffs: Vec<f64>, basis: Basis) -> Self {
+        assert!(!coeffs.is_empty());
+        Self { coeffs, basis }
+    }
+
+    pub fn monomial(coeffs: Vec<f64>) -> Self {
+        Self::new(coeffs, Basis::Monomial)
+    }
+
+    pub fn chebyshev(coeffs: Vec<f64>) -> Self {
+        Self::new(coeffs, Basis::Chebyshev)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coeffs.iter().rposition(|c| *c != 0.0).unwrap_or(0)
+    }
+
+    // 平文での評価
+    pub fn evaluate(&self, x: f64) -> f64 {
+        match self.basis {
+            Basis::Monomial => self.coeffs.iter().rev().fold(0.0, |acc, c| acc * x + c),
+            Basis::Chebyshev => {
+                // Clenshaw
+                let (b1, b2) = self.coeffs[1..]
+                    .iter()
+                    .rev()
+                    .fold((0.0, 0.0), |(b1, b2), c| (2.0 * x * b1 - b2 + c, b1));
+                x * b1 - b2 + self.coeffs[0]
+            }
+        }
+    }
+
+    // 最小の乗算の深さ ⌈log2(d+1)⌉
+    pub fn depth(&self) -> u32 {
+        ceil_log2(self.degree() + 1).max(1)
+    }
+
+    // p = q * B_n + r (deg r < n, deg p < 2n)
+    // T_i = 2 T_n T_{i-n} - T_{2n-i}
+    fn divide(&self, n: usize) -> (Self, Self) {
+        let degree = self.degree();
+        assert!(degree < 2 * n);
+
+        let mut r = self.coeffs[..n.min(self.coeffs.len())].to_vec();
+        let mut q = vec![0.0; degree.saturating_sub(n) + 1];
+        for i in (n..=degree).rev() {
+            let c = self.coeffs[i];
+            match self.basis {
+                Basis::Monomial => q[i - n] = c,
+                Basis::Chebyshev if i == n => q[0] += c,
+                Basis::Chebyshev => {
+                    q[i - n] += 2.0 * c;
+                    r[2 * n - i] -= c;
+                }
+            }
+        }
+
+        (Self::new(q, self.basis), Self::new(r, self.basis))
+    }
+}
+
+// baby step: B_1, ..., B_{k-1} (k = 2^l)
+// giant step: B_k, B_{2k}, ..., B_{2^{m-1} k}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Plan {
+    l: u32,
+    m: u32,
+    depth: u32,
+    multiplications: usize,
+}
+
+impl Plan {
+    fn new(degree: usize, l: u32) -> Self {
+        let k = 1usize << l;
+        let mut m = 0;
+        while k << m <= degree {
+            m += 1;
+        }
+
+        // Σ_{i<k} c_i B_i は定数倍の分だけ 1 段深くなる
+        let babies = degree.min(k - 1);
+        let mut depth = ceil_log2(babies) + 1;
+        for j in 0..m {
+            depth = depth.max(l + j) + 1;
+        }
+        let multiplications = babies.saturating_sub(1) + m as usize + (1 << m) - 1;
+
+        Self {
+            l,
+            m,
+            depth,
+            multiplications,
+        }
+    }
+
+    // 深さが max_depth 以下のうち乗算回数が最小のもの
+    fn choose(degree: usize, max_depth: u32) -> Self {
+        (1..=ceil_log2(degree + 1).max(1))
+            .map(|l| Self::new(degree, l))
+            .filter(|plan| plan.depth <= max_depth)
+            .min_by_key(|plan| (plan.multiplications, plan.depth))
+            .unwrap_or_else(|| panic!("degree {} needs depth > {}", degree, max_depth))
+    }
+}
+
+fn ceil_log2(n: usize) -> u32 {
+    n.next_power_of_two().trailing_zeros()
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    // 最小の深さ (⌈log2(d+1)⌉) で評価する
+    pub fn evaluate_polynomial(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        polynomial: &Polynomial,
+    ) -> Ciphertext<T, N> {
+        self.evaluate_polynomial_with_depth(ciphertext, polynomial, polynomial.depth())
+    }
+
+    // 深さ max_depth 以内で非スカラー乗算が最小になるように baby-step giant-step で評価する
+    // (Paterson–Stockmeyer)
+    pub fn evaluate_polynomial_with_depth(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        polynomial: &Polynomial,
+        max_depth: u32,
+    ) -> Ciphertext<T, N> {
+        let degree = polynomial.degree();
+        let plan = Plan::choose(degree, max_depth);
+        let ciphertext = self.match_scale(ciphertext.clone());
+
+        let k = 1 << plan.l;
+        let mut babies = vec![ciphertext];
+        for i in 2..k.min(degree + 1).max(k / 2 + 1) {
+            babies.push(self.basis_product(&babies, i / 2, i - i / 2, polynomial.basis));
+        }
+        let mut giants = Vec::new();
+        for j in 0..plan.m {
+            let giant = if j == 0 {
+                self.basis_product(&babies, k / 2, k / 2, polynomial.basis)
+            } else {
+                self.basis_double(&giants[j as usize - 1], polynomial.basis)
+            };
+            giants.push(giant);
+        }
+
+        self.evaluate_recursive(polynomial, k, &babies, &giants)
+    }
+
+    // p = q * giants[m-1] + r を再帰的に評価する
+    fn evaluate_recursive(
+        &self,
+        polynomial: &Polynomial,
+        k: usize,
+        babies: &[Ciphertext<T, N>],
+        giants: &[Ciphertext<T, N>],
+    ) -> Ciphertext<T, N> {
+        let degree = polynomial.degree();
+        if degree < k {
+            return self.evaluate_baby(polynomial, babies);
+        }
+
+        let m = giants.len();
+        let n = k << (m - 1);
+        if degree < n {
+            return self.evaluate_recursive(polynomial, k, babies, &giants[..m - 1]);
+        }
+
+        let (q, r) = polynomial.divide(n);
+        let giant = &giants[m - 1];
+        let q = if q.degree() == 0 {
+            self.mul_const(giant.clone(), q.coeffs[0])
+        } else {
+            let q = self.evaluate_recursive(&q, k, babies, &giants[..m - 1]);
+            self.rescale(q * giant.clone())
+        };
+
+        if r.degree() == 0 {
+            self.add_const(q, r.coeffs[0])
+        } else {
+            let r = self.evaluate_recursive(&r, k, babies, &giants[..m - 1]);
+            self.add(&q, &r)
+        }
+    }
+
+    // Σ_{i<k} c_i B_i
+    // 項 c_i B_i の定数を scale S / scale(B_i) で掛けて全項の scale を S に揃えてから1回だけ rescale する
+    // 一番低いレベル l で f_l で割るので、S = Δ_{l-1} f_l にすると結果の scale は Δ_{l-1} になる
+    fn evaluate_baby(
+        &self,
+        polynomial: &Polynomial,
+        babies: &[Ciphertext<T, N>],
+    ) -> Ciphertext<T, N> {
+        let terms: Vec<(usize, f64)> = polynomial
+            .coeffs
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|&(_, &c)| c != 0.0)
+            .map(|(i, &c)| (i, c))
+            .collect();
+        let level = terms
+            .iter()
+            .map(|&(i, _)| self.level(&babies[i - 1]))
+            .min()
+            .unwrap_or_else(|| self.level(&babies[0]));
+        let factor = self.params.factor(level).to_f64().unwrap();
+        let target = self.params.level_scale(level - 1) * factor;
+        let term = |i: usize, c: f64| {
+            let baby = &babies[i - 1];
+            baby.clone() * constant(c, target / baby.scale, baby.encoding)
+        };
+
+        let result = terms
+            .iter()
+            .map(|&(i, c)| term(i, c))
+            .reduce(|acc, term| self.add(&acc, &term))
+            .unwrap_or_else(|| term(1, 0.0));
+
+        self.add_const(self.rescale(result), polynomial.coeffs[0])
+    }
+
+    // B_{a+b} を B_a, B_b から作る (babies[i-1] = B_i)
+    // x^{a+b} = x^a x^b
+    // T_{a+b} = 2 T_a T_b - T_{b-a}
+    fn basis_product(
+        &self,
+        babies: &[Ciphertext<T, N>],
+        a: usize,
+        b: usize,
+        basis: Basis,
+    ) -> Ciphertext<T, N> {
+        let product = self.rescale(babies[a - 1].clone() * babies[b - 1].clone());
+        match basis {
+            Basis::Monomial => product,
+            Basis::Chebyshev => {
+                let doubled = product.clone() + product;
+                if a == b {
+                    self.add_const(doubled, -1.0)
+                } else {
+                    self.sub(&doubled, &babies[b - a - 1])
+                }
+            }
+        }
+    }
+
+    // B_{2n} を B_n から作る
+    fn basis_double(&self, ciphertext: &Ciphertext<T, N>, basis: Basis) -> Ciphertext<T, N> {
+        let product = self.rescale(ciphertext.clone() * ciphertext.clone());
+        match basis {
+            Basis::Monomial => product,
+            Basis::Chebyshev => self.add_const(product.clone() + product, -1.0),
+        }
+    }
+
+    // scale を今のレベルの Δ_l に揃える (違うときだけ1レベル使う)
+    pub(crate) fn match_scale(&self, ciphertext: Ciphertext<T, N>) -> Ciphertext<T, N> {
+        let target = self.params.level_scale(self.level(&ciphertext));
+        if scales_match(ciphertext.scale, target) {
+            return ciphertext;
+        }
+
+        self.adjust_scale(ciphertext, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{self, params::Params, testing::Context};
+    use num_complex::Complex64;
+
+    const N: usize = 8;
+
+    fn check(polynomial: &Polynomial, max_depth: u32) -> f64 {
+        check_with(
+            Params::new(5, 1 << 16, 1 << 20, 1 << 16),
+            polynomial,
+            max_depth,
+        )
+    }
+
+    fn check_with(params: Params<i128>, polynomial: &Polynomial, max_depth: u32) -> f64 {
+        let context = Context::<N>::new(params.clone(), &[]);
+        let evaluator = &context.evaluator;
+
+        let x = [0.9, -0.5, 0.1, -0.8];
+        let ciphertext = context.encrypt(&x.map(|x| Complex64::new(x, 0.0)));
+        let result = evaluator.evaluate_polynomial_with_depth(&ciphertext, polynomial, max_depth);
+        assert!(params.limit - evaluator.level(&result) <= max_depth);
+
+        let expected = x.map(|x| Complex64::new(polynomial.evaluate(x), 0.0));
+        ckks::precision_bits(&expected, &context.decrypt(&result))
+    }
+
+    #[test]
+    fn divide() {
+        // T_3 + 2 T_2 + T_1 を T_2 で割る
+        let polynomial = Polynomial::chebyshev(vec![0.0, 1.0, 2.0, 1.0]);
+        let (q, r) = polynomial.divide(2);
+        for x in [-1.0, -0.3, 0.2, 0.7] {
+            let t2 = 2.0 * x * x - 1.0;
+            let expected = polynomial.evaluate(x);
+            assert!((q.evaluate(x) * t2 + r.evaluate(x) - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn plan() {
+        // k = 2 なら深さは ⌈log2(d+1)⌉
+        assert_eq!(Plan::choose(15, 4).l, 1);
+        // 深さに余裕があれば乗算の少ない baby step を選ぶ
+        let plan = Plan::choose(15, 5);
+        assert!(plan.multiplications < Plan::new(15, 1).multiplications);
+        assert_eq!(plan.depth, 5);
+    }
+
+    #[test]
+    fn monomial() {
+        let polynomial = Polynomial::monomial(vec![0.5, -1.0, 0.0, 2.0, 0.25, -0.5, 1.0]);
+        assert_eq!(polynomial.depth(), 3);
+        assert!(check(&polynomial, 3) > 6.0);
+        assert!(check(&polynomial, 4) > 6.0);
+    }
+
+    #[test]
+    fn chebyshev() {
+        let polynomial = Polynomial::chebyshev((0..16).map(|i| 1.0 / (i + 1) as f64).collect());
+        assert_eq!(polynomial.depth(), 4);
+        assert!(check(&polynomial, 4) > 8.0);
+        assert!(check(&polynomial, 5) > 8.0);
+    }
+
+    #[test]
+    fn factors() {
+        // rescale ごとに scale がずれても panic せず、深さも変わらない
+        let factors = vec![65519, 65543, 65521, 65537, 65531];
+        let params = Params::new(5, 1 << 16, 1 << 20, 1 << 16).with_factors(factors);
+        let monomial = Polynomial::monomial(vec![0.5, -1.0, 0.0, 2.0, 0.25, -0.5, 1.0]);
+        let chebyshev = Polynomial::chebyshev((0..16).map(|i| 1.0 / (i + 1) as f64).collect());
+        for params in [params.clone(), params.with_exact_scales()] {
+            assert!(check_with(params.clone(), &monomial, 3) > 6.0);
+            assert!(check_with(params.clone(), &monomial, 4) > 6.0);
+            assert!(check_with(params.clone(), &chebyshev, 4) > 8.0);
+            assert!(check_with(params, &chebyshev, 5) > 8.0);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use super::{
+    ciphertext::Ciphertext,
+    code::{galois_element, real_galois_element},
+    encoder::{EncodeError, Encoder},
+    evaluator::Evaluator,
+    plaintext::{Encoding, Plaintext},
+};
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::fmt::Debug;
+
+// block 個ずつの部分和に必要な Galois 鍵 (5^1, 5^2, 5^4, ...)
+pub fn sum_galois_elements<const N: usize>(block: usize) -> Vec<usize> {
+    assert_block(block, N / 2);
+    (0..block.trailing_zeros())
+        .map(|j| galois_element::<N>(1 << j))
+        .collect()
+}
+
+// 実数スロット (共役不変環) 用 (5^1, 5^2, 5^4, ... mod 4N)
+pub fn sum_real_galois_elements<const N: usize>(block: usize) -> Vec<usize> {
+    assert_block(block, N);
+    (0..block.trailing_zeros())
+        .map(|j| real_galois_element::<N>(1 << j))
+        .collect()
+}
+
+fn assert_block(block: usize, max: usize) {
+    assert!(
+        block.is_power_of_two() && block <= max,
+        "block size {} must be a power of two <= {}",
+        block,
+        max
+    );
+}
+
+impl<T: Integer + NumCast + Default + Copy + Debug, const N: usize> Evaluator<T, N> {
+    // i 番目のスロットに Σ_{t < block} z_{i+t} (添字はスロット数 N/2、実数スロットなら N を法として) を入れる
+    // log2(block) 回の回転と加算で計算する
+    // c <- c + rotate(c, 2^j)
+    pub fn partial_sum(&self, ciphertext: &Ciphertext<T, N>, block: usize) -> Ciphertext<T, N> {
+        let max = if ciphertext.encoding == Encoding::Real {
+            N
+        } else {
+            N / 2
+        };
+        assert_block(block, max);
+
+        (0..block.trailing_zeros()).fold(ciphertext.clone(), |acc, j| {
+            let rotated = self.rotate(&acc, 1 << j);
+            acc + rotated
+        })
+    }
+
+    // 全スロットに総和を入れる (sparse packing なら log2(n) 回の回転で済む)
+    pub fn sum_slots(&self, ciphertext: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        self.partial_sum(ciphertext, ciphertext.slots)
+    }
+
+    // Σ a_i b_i を全スロットに入れる
+    pub fn inner_product(&self, a: &Ciphertext<T, N>, b: &Ciphertext<T, N>) -> Ciphertext<T, N> {
+        self.sum_slots(&self.rescale(a.clone() * b.clone()))
+    }
+
+    // Σ a_i b_i (b は平文のベクトル、a と同じ符号化で scale p に符号化する)
+    pub fn inner_product_plain(
+        &self,
+        a: &Ciphertext<T, N>,
+        b: &[Complex64],
+    ) -> Result<Ciphertext<T, N>, EncodeError> {
+        let b = self.encode_for(a, b)?;
+        Ok(self.sum_slots(&self.rescale(a.clone() * b)))
+    }
+
+    // slot 番目の値を全スロットに複製する
+    // その他のスロットを 0 にしてから総和を取る
+    pub fn broadcast(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        slot: usize,
+    ) -> Result<Ciphertext<T, N>, EncodeError> {
+        assert!(slot < ciphertext.slots);
+
+        let mut mask = vec![Complex64::default(); ciphertext.slots];
+        mask[slot] = Complex64::new(1.0, 0.0);
+        let mask = self.encode_for(ciphertext, &mask)?;
+
+        Ok(self.sum_slots(&self.rescale(ciphertext.clone() * mask)))
+    }
+
+    // ciphertext に掛ける平文 (scale p、法 q_l、ciphertext の scale を掛けても溢れない)
+    // 係数の符号化の暗号文にはスロットがないのでエラー
+    fn encode_for(
+        &self,
+        ciphertext: &Ciphertext<T, N>,
+        z: &[Complex64],
+    ) -> Result<Plaintext<T, N>, EncodeError> {
+        let encoder = Encoder::new(self.params.p.to_f64().unwrap())
+            .with_modulo(ciphertext.modulo())
+            .with_headroom(ciphertext.scale);
+
+        match ciphertext.encoding {
+            Encoding::Slots => encoder.encode(z),
+            Encoding::Real => {
+                if let Some(index) = z.iter().position(|z| z.im != 0.0) {
+                    return Err(EncodeError::NotReal {
+                        index,
+                        imaginary: z[index].im,
+                    });
+                }
+                encoder.encode_real(&z.iter().map(|z| z.re).collect::<Vec<_>>())
+            }
+            Encoding::Coefficients => Err(EncodeError::EncodingMismatch {
+                expected: Encoding::Slots,
+                found: Encoding::Coefficients,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{self, params::Params, poly::Ring, testing::Context};
+
+    const N: usize = 16;
+
+    fn context() -> Context<N> {
+        let params = Params::<i128>::new(2, 1 << 16, 1 << 20, 1 << 16);
+        Context::new(params, &sum_galois_elements::<N>(N / 2))
+    }
+
+    fn vector(offset: f64) -> [Complex64; N / 2] {
+        std::array::from_fn(|i| Complex64::new(offset + 0.1 * i as f64, 0.05 * i as f64 - offset))
+    }
+
+    // Σ_{t < block} z_{i+t}
+    fn partial_sum(z: [Complex64; N / 2], block: usize) -> [Complex64; N / 2] {
+        std::array::from_fn(|i| (0..block).map(|t| z[(i + t) % (N / 2)]).sum())
+    }
+
+    #[test]
+    fn sum_slots() {
+        let context = context();
+        let z = vector(0.3);
+        let ciphertext = context.encrypt(&z);
+
+        for block in [1, 2, 4, 8] {
+            let result = context.evaluator.partial_sum(&ciphertext, block);
+            let expected = partial_sum(z, block);
+            assert!(ckks::precision_bits(&expected, &context.decrypt(&result)) > 7.0);
+        }
+        let result = context.evaluator.sum_slots(&ciphertext);
+        let expected = partial_sum(z, N / 2);
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&result)) > 7.0);
+    }
+
+    #[test]
+    fn inner_product() {
+        let context = context();
+        let (a, b) = (vector(0.3), vector(-0.2));
+        let product = std::array::from_fn(|i| a[i] * b[i]);
+        let expected = partial_sum(product, N / 2);
+
+        let (ca, cb) = (context.encrypt(&a), context.encrypt(&b));
+        let result = context.evaluator.inner_product(&ca, &cb);
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&result)) > 7.0);
+
+        let result = context.evaluator.inner_product_plain(&ca, &b).unwrap();
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&result)) > 7.0);
+    }
+
+    #[test]
+    fn broadcast() {
+        let context = context();
+        let z = vector(0.3);
+        let ciphertext = context.encrypt(&z);
+
+        let slot = 3;
+        let result = context.evaluator.broadcast(&ciphertext, slot).unwrap();
+        let expected = [z[slot]; N / 2];
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&result)) > 7.0);
+    }
+
+    #[test]
+    fn sparse_sum_slots() {
+        let context = context();
+        let z = [Complex64::new(0.3, -0.1), Complex64::new(-0.2, 0.4)];
+        let ciphertext = context.encrypt(&z);
+
+        // 2 スロットなら回転 1 回
+        let result = context.evaluator.sum_slots(&ciphertext);
+        let decrypted = context.decrypt(&result);
+        assert_eq!(decrypted.len(), 2);
+        assert!(ckks::precision_bits(&[z[0] + z[1]; 2], &decrypted) > 7.0);
+
+        let result = context.evaluator.broadcast(&ciphertext, 1).unwrap();
+        assert!(ckks::precision_bits(&[z[1]; 2], &context.decrypt(&result)) > 7.0);
+    }
+
+    #[test]
+    fn real_slots() {
+        let params =
+            Params::<i128>::new(2, 1 << 16, 1 << 20, 1 << 16).with_ring(Ring::ConjugateInvariant);
+        let context = Context::<N>::new(params.clone(), &sum_real_galois_elements::<N>(N));
+        let evaluator = &context.evaluator;
+
+        // N 個の実数スロットの総和と内積
+        // 誤差は 500 回で最大 1.7e-2 (総和)、9.8e-3 (内積)、4.9e-3 (複製)
+        let x: [f64; N] = std::array::from_fn(|i| 0.1 * i as f64 - 0.7);
+        let plaintext = ckks::encode_real::<i128, N>(&x, params.p as f64);
+        let ciphertext = ckks::encrypt(plaintext, context.public_key, context.evaluation_key);
+        let decrypt = |c| ckks::decode_real(ckks::decrypt(c, &context.secret_key));
+
+        let sum: f64 = x.iter().sum();
+        for a in decrypt(evaluator.sum_slots(&ciphertext)) {
+            assert!((a - sum).abs() < 3e-2);
+        }
+
+        let b = x.map(|x| Complex64::new(0.5 - x, 0.0));
+        let dot: f64 = x.iter().zip(&b).map(|(x, b)| x * b.re).sum();
+        let result = evaluator.inner_product_plain(&ciphertext, &b).unwrap();
+        for a in decrypt(result) {
+            assert!((a - dot).abs() < 2e-2);
+        }
+
+        for a in decrypt(evaluator.broadcast(&ciphertext, 5).unwrap()) {
+            assert!((a - x[5]).abs() < 1e-2);
+        }
+
+        // 実数スロットに複素数は入らない
+        let mut b = b;
+        b[2].im = 0.5;
+        assert_eq!(
+            evaluator.inner_product_plain(&ciphertext, &b).err(),
+            Some(EncodeError::NotReal {
+                index: 2,
+                imaginary: 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn coefficients_have_no_slots() {
+        let context = context();
+        let params = &context.evaluator.params;
+        let plaintext = ckks::encode_coeffs::<i128, N>(&[0.5, -0.25], params.p as f64);
+        let ciphertext = ckks::encrypt(plaintext, context.public_key, context.evaluation_key);
+
+        assert_eq!(
+            context.evaluator.broadcast(&ciphertext, 0).err(),
+            Some(EncodeError::EncodingMismatch {
+                expected: Encoding::Slots,
+                found: Encoding::Coefficients
+            })
+        );
+    }
+}
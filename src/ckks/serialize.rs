@@ -0,0 +1,957 @@
+// 鍵・暗号文・平文のバイナリ形式 (version 1, little endian)
+//
+// header (38 bytes)
+//   0..4    magic "CKKS"
+//   4       version
+//   5       kind (1: SecretKey, 2: PublicKey, 3: EvaluationKey, 4: Ciphertext, 5: Plaintext)
+//   6..14   Params::fingerprint
+//   14..22  KeyId (平文は 0)
+//   22..26  N (u32)
+//   26..30  level l (u32)
+//   30..38  scale (f64, 鍵は EvaluationKey の P 以外 0)
+//
+// body
+//   SecretKey      s
+//   PublicKey      b, a
+//   EvaluationKey  modulus (i128), b, a
+//   Ciphertext     slots (u32), encoding (u8), 成分の数 (u8), c0, c1, ...
+//   Plaintext      slots (u32), encoding (u8), m
+//
+// 多項式は法 q の中心化した係数 (-q/2, q/2] を ⌈log2 q⌉ ビットずつ詰めたもの
+// q は鍵が q_L、EvaluationKey が body の modulus、暗号文と平文が q_l
+
+use super::{
+    ciphertext::Ciphertext,
+    compress::{pack_bits, unpack_bits},
+    keys::{EvaluationKey, KeyId, KeyMismatch, PublicKey, SecretKey},
+    modulo::is_in_range,
+    params::Params,
+    plaintext::{Encoding, Plaintext},
+    poly::{ModPoly, Poly},
+};
+use num_integer::Integer;
+use num_traits::NumCast;
+use std::fmt::{self, Debug};
+use zeroize::{Zeroize, Zeroizing};
+
+pub const MAGIC: [u8; 4] = *b"CKKS";
+pub const VERSION: u8 = 1;
+const HEADER_LEN: usize = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    SecretKey = 1,
+    PublicKey = 2,
+    EvaluationKey = 3,
+    Ciphertext = 4,
+    Plaintext = 5,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    Truncated { needed: usize, available: usize },
+    TrailingBytes { extra: usize },
+    BadMagic,
+    UnsupportedVersion(u8),
+    WrongKind { expected: Kind, found: u8 },
+    // 別の Params で保存された
+    ParamsMismatch { expected: u64, found: u64 },
+    DimensionMismatch { expected: usize, found: u32 },
+    // 渡した評価鍵と暗号文の鍵一式が違う
+    KeyMismatch(KeyMismatch),
+    // 鍵と暗号文の KeyId が 0、または平文の KeyId が 0 でない
+    InvalidKeyId(KeyId),
+    InvalidLevel { level: u32, limit: u32 },
+    // EvaluationKey の法が P q_L でない
+    InvalidModulus,
+    // 暗号文の法がどの q_l でもない
+    ModulusNotInChain,
+    InvalidSlots(u32),
+    InvalidEncoding(u8),
+    // 成分の数が 2 未満か 255 を超える
+    InvalidComponents(usize),
+    // 係数が (-q/2, q/2] に入っていない
+    CoefficientOutOfRange { index: usize },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncated { needed, available } => {
+                write!(
+                    f,
+                    "input truncated: need {} bytes but {} left",
+                    needed, available
+                )
+            }
+            Self::TrailingBytes { extra } => write!(f, "{} unexpected trailing bytes", extra),
+            Self::BadMagic => write!(f, "not a serialized CKKS object"),
+            Self::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "format version {} is not supported (expected {})",
+                    version, VERSION
+                )
+            }
+            Self::WrongKind { expected, found } => {
+                write!(f, "expected a {:?} but found kind {}", expected, found)
+            }
+            Self::ParamsMismatch { expected, found } => write!(
+                f,
+                "serialized with params {:016x} but loading with {:016x}",
+                found, expected
+            ),
+            Self::DimensionMismatch { expected, found } => {
+                write!(
+                    f,
+                    "ring dimension {} does not match N = {}",
+                    found, expected
+                )
+            }
+            Self::KeyMismatch(error) => write!(f, "{}", error),
+            Self::InvalidKeyId(id) => write!(f, "invalid key id {:016x}", id.0),
+            Self::InvalidLevel { level, limit } => {
+                write!(f, "level {} is above the limit {}", level, limit)
+            }
+            Self::InvalidModulus => write!(f, "modulus is not P q_L"),
+            Self::ModulusNotInChain => write!(f, "modulus is not in the modulus chain"),
+            Self::InvalidSlots(slots) => write!(f, "invalid number of slots {}", slots),
+            Self::InvalidEncoding(encoding) => write!(f, "unknown encoding {}", encoding),
+            Self::InvalidComponents(count) => {
+                write!(f, "a ciphertext cannot have {} components", count)
+            }
+            Self::CoefficientOutOfRange { index } => {
+                write!(f, "coefficient {} is outside (-q/2, q/2]", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl<T: Integer + Zeroize + Copy + Default + NumCast, const N: usize> SecretKey<T, N> {
+    pub fn to_bytes(&self, params: &Params<T>) -> Vec<u8> {
+        let mut bytes = header::<T, N>(Kind::SecretKey, params, self.id, params.limit, 0.0);
+        bytes.extend(pack_polys(std::slice::from_ref(self.expose_secret())));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params<T>) -> Result<Self, FormatError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<T, N>(Kind::SecretKey, params)?;
+        let s = Zeroizing::new(reader.poly_vec(params.modulo(header.level), 1)?);
+        reader.finish()?;
+        let mut secret_key = Self::from_secret(s[0], header.key_id);
+        secret_key.ring = params.ring;
+        Ok(secret_key)
+    }
+}
+
+impl<T: Integer + Copy + Default + NumCast, const N: usize> PublicKey<T, N> {
+    pub fn to_bytes(&self, params: &Params<T>) -> Vec<u8> {
+        let mut bytes = header::<T, N>(Kind::PublicKey, params, self.id, params.limit, 0.0);
+        bytes.extend(pack_polys(&[self.b, self.a]));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params<T>) -> Result<Self, FormatError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<T, N>(Kind::PublicKey, params)?;
+        let [b, a] = reader.polys(params.modulo(header.level))?;
+        reader.finish()?;
+        Ok(Self {
+            b,
+            a,
+            id: header.key_id,
+            ring: params.ring,
+        })
+    }
+}
+
+impl<T: Integer + Copy + Default + NumCast, const N: usize> EvaluationKey<T, N> {
+    pub fn to_bytes(&self, params: &Params<T>) -> Vec<u8> {
+        let scale = self.scale.to_f64().unwrap();
+        let mut bytes = header::<T, N>(Kind::EvaluationKey, params, self.id, params.limit, scale);
+        bytes.extend(self.b.modulo.to_i128().unwrap().to_le_bytes());
+        bytes.extend(pack_polys(&[self.b, self.a]));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params<T>) -> Result<Self, FormatError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<T, N>(Kind::EvaluationKey, params)?;
+
+        let modulo = i128::from_le_bytes(reader.array()?);
+        let modulo = T::from(modulo).ok_or(FormatError::InvalidModulus)?;
+        if modulo != params.scale * params.modulo(params.limit) {
+            return Err(FormatError::InvalidModulus);
+        }
+        let [b, a] = reader.polys(modulo)?;
+        reader.finish()?;
+
+        Ok(Self {
+            b,
+            a,
+            scale: params.scale,
+            id: header.key_id,
+            ring: params.ring,
+        })
+    }
+}
+
+impl<T: Integer + Copy + Default + Debug + NumCast, const N: usize> Ciphertext<T, N> {
+    // 法が q_l のどれでもないか、成分が 255 個を超えるとエラー
+    pub fn to_bytes(&self, params: &Params<T>) -> Result<Vec<u8>, FormatError> {
+        let level = params
+            .try_level(self.modulo())
+            .ok_or(FormatError::ModulusNotInChain)?;
+        let count =
+            u8::try_from(self.c.len()).map_err(|_| FormatError::InvalidComponents(self.c.len()))?;
+
+        let mut bytes = header::<T, N>(Kind::Ciphertext, params, self.key_id, level, self.scale);
+        bytes.extend((self.slots as u32).to_le_bytes());
+        bytes.push(encoding_to_byte(self.encoding));
+        bytes.push(count);
+        bytes.extend(pack_polys(&self.c));
+        Ok(bytes)
+    }
+
+    // 評価鍵は保存しないので渡す (保存した KeyId と違えばエラー)
+    pub fn from_bytes(
+        bytes: &[u8],
+        params: &Params<T>,
+        evaluation_key: EvaluationKey<T, N>,
+    ) -> Result<Self, FormatError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<T, N>(Kind::Ciphertext, params)?;
+        header
+            .key_id
+            .check(evaluation_key.id)
+            .map_err(FormatError::KeyMismatch)?;
+        let (slots, encoding) = reader.slots::<N>()?;
+        let count = reader.array::<1>()?[0];
+        if count < 2 {
+            return Err(FormatError::InvalidComponents(count as usize));
+        }
+        let c = reader.poly_vec(params.modulo(header.level), count as usize)?;
+        reader.finish()?;
+
+        Ok(Self {
+            slots,
+            encoding,
+            ..Self::from_components(c, evaluation_key, header.scale)
+        })
+    }
+}
+
+impl<T: Integer + Copy + Default + NumCast, const N: usize> Plaintext<T, N> {
+    // m を q_level で取り直して保存する
+    pub fn to_bytes(&self, params: &Params<T>, level: u32) -> Vec<u8> {
+        let m = self.mod_switch_to(params, level).m;
+        let modulo = params.modulo(level);
+
+        let mut bytes =
+            header::<T, N>(Kind::Plaintext, params, KeyId::default(), level, self.scale);
+        bytes.extend((self.slots as u32).to_le_bytes());
+        bytes.push(encoding_to_byte(self.encoding));
+        bytes.extend(pack_polys(&[ModPoly::new(m.coeffs, modulo)]));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params<T>) -> Result<Self, FormatError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<T, N>(Kind::Plaintext, params)?;
+        let (slots, encoding) = reader.slots::<N>()?;
+        let [m] = reader.polys(params.modulo(header.level))?;
+        reader.finish()?;
+
+        Ok(Self {
+            m: Poly::new(m.coeffs),
+            scale: header.scale,
+            slots,
+            encoding,
+            modulo: Some(params.modulo(header.level)),
+        })
+    }
+}
+
+struct Header {
+    key_id: KeyId,
+    level: u32,
+    scale: f64,
+}
+
+fn header<T: Integer + Copy + NumCast, const N: usize>(
+    kind: Kind,
+    params: &Params<T>,
+    key_id: KeyId,
+    level: u32,
+    scale: f64,
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(kind as u8);
+    bytes.extend(params.fingerprint().to_le_bytes());
+    bytes.extend(key_id.0.to_le_bytes());
+    bytes.extend((N as u32).to_le_bytes());
+    bytes.extend(level.to_le_bytes());
+    bytes.extend(scale.to_le_bytes());
+    bytes
+}
+
+fn encoding_to_byte(encoding: Encoding) -> u8 {
+    match encoding {
+        Encoding::Slots => 0,
+        Encoding::Coefficients => 1,
+        Encoding::Real => 2,
+    }
+}
+
+// ⌈log2 q⌉
+fn coefficient_bits<T: NumCast>(modulo: T) -> u32 {
+    128 - (modulo.to_i128().unwrap() - 1).leading_zeros()
+}
+
+// 同じ法の多項式を続けて詰める (秘密鍵も通るので途中の係数は消す)
+fn pack_polys<T: Integer + Copy + NumCast, const N: usize>(polys: &[ModPoly<T, N>]) -> Vec<u8> {
+    let values = Zeroizing::new(
+        polys
+            .iter()
+            .flat_map(|p| p.coeffs)
+            .map(|x| x.to_i128().unwrap())
+            .collect::<Vec<_>>(),
+    );
+    pack_bits(&values, coefficient_bits(polys[0].modulo))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let available = self.bytes.len() - self.position;
+        if len > available {
+            return Err(FormatError::Truncated {
+                needed: len,
+                available,
+            });
+        }
+
+        let taken = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(taken)
+    }
+
+    fn array<const L: usize>(&mut self) -> Result<[u8; L], FormatError> {
+        Ok(self.take(L)?.try_into().unwrap())
+    }
+
+    fn finish(self) -> Result<(), FormatError> {
+        match self.bytes.len() - self.position {
+            0 => Ok(()),
+            extra => Err(FormatError::TrailingBytes { extra }),
+        }
+    }
+
+    fn header<T: Integer + Copy + NumCast, const N: usize>(
+        &mut self,
+        kind: Kind,
+        params: &Params<T>,
+    ) -> Result<Header, FormatError> {
+        if self.array::<4>()? != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        let [version] = self.array()?;
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let [found] = self.array()?;
+        if found != kind as u8 {
+            return Err(FormatError::WrongKind {
+                expected: kind,
+                found,
+            });
+        }
+
+        let expected = params.fingerprint();
+        let found = u64::from_le_bytes(self.array()?);
+        if found != expected {
+            return Err(FormatError::ParamsMismatch { expected, found });
+        }
+        let key_id = KeyId(u64::from_le_bytes(self.array()?));
+        if (kind == Kind::Plaintext) != (key_id == KeyId::default()) {
+            return Err(FormatError::InvalidKeyId(key_id));
+        }
+        let found = u32::from_le_bytes(self.array()?);
+        if found as usize != N {
+            return Err(FormatError::DimensionMismatch { expected: N, found });
+        }
+        let level = u32::from_le_bytes(self.array()?);
+        if level > params.limit {
+            return Err(FormatError::InvalidLevel {
+                level,
+                limit: params.limit,
+            });
+        }
+        let scale = f64::from_le_bytes(self.array()?);
+
+        Ok(Header {
+            key_id,
+            level,
+            scale,
+        })
+    }
+
+    // スロット数と符号化 (Real なら N 個、それ以外は N/2 個まで)
+    fn slots<const N: usize>(&mut self) -> Result<(usize, Encoding), FormatError> {
+        let slots = u32::from_le_bytes(self.array()?);
+        let encoding = self.encoding()?;
+        let max = if encoding == Encoding::Real { N } else { N / 2 };
+        if slots == 0 || !max.is_multiple_of(slots as usize) {
+            return Err(FormatError::InvalidSlots(slots));
+        }
+        Ok((slots as usize, encoding))
+    }
+
+    fn encoding(&mut self) -> Result<Encoding, FormatError> {
+        match self.array::<1>()? {
+            [0] => Ok(Encoding::Slots),
+            [1] => Ok(Encoding::Coefficients),
+            [2] => Ok(Encoding::Real),
+            [other] => Err(FormatError::InvalidEncoding(other)),
+        }
+    }
+
+    fn polys<T, const N: usize, const K: usize>(
+        &mut self,
+        modulo: T,
+    ) -> Result<[ModPoly<T, N>; K], FormatError>
+    where
+        T: Integer + Copy + Default + NumCast,
+    {
+        let polys = self.poly_vec(modulo, K)?;
+        Ok(std::array::from_fn(|i| polys[i]))
+    }
+
+    fn poly_vec<T, const N: usize>(
+        &mut self,
+        modulo: T,
+        count: usize,
+    ) -> Result<Vec<ModPoly<T, N>>, FormatError>
+    where
+        T: Integer + Copy + Default + NumCast,
+    {
+        let bits = coefficient_bits(modulo);
+        let data = self.take((count * N * bits as usize).div_ceil(8))?;
+        // 秘密鍵の係数も通るので読み終えたら消す
+        let values = Zeroizing::new(unpack_bits(data, bits, count * N));
+
+        values
+            .chunks(N)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut coeffs = [T::zero(); N];
+                for (j, (c, &value)) in coeffs.iter_mut().zip(chunk).enumerate() {
+                    let index = i * N + j;
+                    let x = T::from(value).ok_or(FormatError::CoefficientOutOfRange { index })?;
+                    if !is_in_range(x, modulo) {
+                        return Err(FormatError::CoefficientOutOfRange { index });
+                    }
+                    *c = x;
+                }
+                Ok(ModPoly::new(coeffs, modulo))
+            })
+            .collect()
+    }
+}
+
+// serde (feature = "serde") では多項式の係数を同じように詰めて
+// JSON などでは 16 進文字列、CBOR などではバイト列にする
+//   Poly     {"bits": b, "coeffs": ...} (b は係数が (-2^{b-1}, 2^{b-1}] に入る最小のビット数)
+//   ModPoly  {"modulo": q, "coeffs": ...} (⌈log2 q⌉ ビット、q は JSON では文字列)
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::{Zeroizing, coefficient_bits, is_in_range, pack_bits, unpack_bits};
+    use crate::ckks::{
+        ciphertext::Ciphertext,
+        keys::{EvaluationKey, KeyId},
+        plaintext::Encoding,
+        poly::{ModPoly, Poly},
+    };
+    use num_integer::Integer;
+    use num_traits::NumCast;
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{self, Visitor},
+        ser::SerializeStruct,
+    };
+    use std::fmt;
+
+    struct Bytes(Vec<u8>);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                let hex: String = self.0.iter().map(|b| format!("{:02x}", b)).collect();
+                serializer.serialize_str(&hex)
+            } else {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BytesVisitor;
+
+            impl<'de> Visitor<'de> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "packed coefficients as bytes or a hex string")
+                }
+
+                fn visit_str<E: de::Error>(self, hex: &str) -> Result<Bytes, E> {
+                    if !hex.len().is_multiple_of(2) {
+                        return Err(E::custom("hex string has odd length"));
+                    }
+                    (0..hex.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("x"), 16))
+                        .collect::<Result<_, _>>()
+                        .map(Bytes)
+                        .map_err(|_| E::custom("invalid hex string"))
+                }
+
+                fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Bytes, E> {
+                    Ok(Bytes(bytes.to_vec()))
+                }
+
+                fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(bytes))
+                }
+
+                fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+                    let mut bytes = Vec::new();
+                    while let Some(b) = seq.next_element()? {
+                        bytes.push(b);
+                    }
+                    Ok(Bytes(bytes))
+                }
+            }
+
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_str(BytesVisitor)
+            } else {
+                deserializer.deserialize_bytes(BytesVisitor)
+            }
+        }
+    }
+
+    // JSON の数は 64 ビットに収まらないことがあるので文字列にする
+    struct Wide(i128);
+
+    impl Serialize for Wide {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.serialize_str(&self.0.to_string())
+            } else {
+                serializer.serialize_i128(self.0)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Wide {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map(Wide).map_err(de::Error::custom)
+            } else {
+                i128::deserialize(deserializer).map(Wide)
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "Poly")]
+    struct PolyRepr {
+        bits: u32,
+        coeffs: Bytes,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "ModPoly")]
+    struct ModPolyRepr {
+        modulo: Wide,
+        coeffs: Bytes,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "Ciphertext")]
+    #[serde(bound(
+        deserialize = "T: num_integer::Integer + Copy + num_traits::NumCast + serde::Deserialize<'de>"
+    ))]
+    struct CiphertextRepr<T: Integer, const N: usize> {
+        c: Vec<ModPoly<T, N>>,
+        evaluation_key: EvaluationKey<T, N>,
+        key_id: KeyId,
+        scale: f64,
+        slots: usize,
+        encoding: Encoding,
+    }
+
+    impl<'de, T: Integer + Copy + NumCast, const N: usize> Deserialize<'de> for Ciphertext<T, N>
+    where
+        T: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = CiphertextRepr::deserialize(deserializer)?;
+            // 評価鍵も一緒に読むので、別の鍵一式のものなら読み込み時にエラーにする
+            repr.key_id
+                .check(repr.evaluation_key.id)
+                .map_err(de::Error::custom)?;
+            if repr.c.len() < 2 {
+                return Err(de::Error::invalid_length(
+                    repr.c.len(),
+                    &"at least 2 components",
+                ));
+            }
+            Ok(Ciphertext {
+                c: repr.c,
+                evaluation_key: repr.evaluation_key,
+                key_id: repr.key_id,
+                scale: repr.scale,
+                slots: repr.slots,
+                encoding: repr.encoding,
+            })
+        }
+    }
+
+    // 秘密鍵の係数も通るので使い終えたら消す
+    fn to_i128<T: Copy + NumCast, const N: usize>(coeffs: &[T; N]) -> Zeroizing<Vec<i128>> {
+        Zeroizing::new(coeffs.iter().map(|x| x.to_i128().unwrap()).collect())
+    }
+
+    // bits ビットで詰めた N 個の係数
+    fn unpack<T: Integer + Copy + NumCast, E: de::Error, const N: usize>(
+        coeffs: &Bytes,
+        bits: u32,
+    ) -> Result<[T; N], E> {
+        if bits == 0 || bits > 128 {
+            return Err(E::custom(format!("invalid coefficient width {}", bits)));
+        }
+        let expected = (N * bits as usize).div_ceil(8);
+        if coeffs.0.len() != expected {
+            return Err(E::invalid_length(coeffs.0.len(), &&*expected.to_string()));
+        }
+
+        let values = Zeroizing::new(unpack_bits(&coeffs.0, bits, N));
+        let mut unpacked = [T::zero(); N];
+        for (x, &value) in unpacked.iter_mut().zip(values.iter()) {
+            *x = T::from(value)
+                .ok_or_else(|| E::custom("coefficient does not fit in the integer type"))?;
+        }
+        Ok(unpacked)
+    }
+
+    impl<T: Integer + Copy + NumCast, const N: usize> Serialize for Poly<T, N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            // |x| < 2^{bits-1} になる幅、128 ビットでは 2 の補数で i128 全体をそのまま詰める
+            let values = to_i128(&self.coeffs);
+            let bits = values
+                .iter()
+                .map(|x| 129 - x.unsigned_abs().leading_zeros())
+                .max()
+                .unwrap_or(1)
+                .min(128);
+
+            let mut state = serializer.serialize_struct("Poly", 2)?;
+            state.serialize_field("bits", &bits)?;
+            state.serialize_field("coeffs", &Bytes(pack_bits(&values, bits)))?;
+            state.end()
+        }
+    }
+
+    impl<'de, T: Integer + Copy + NumCast, const N: usize> Deserialize<'de> for Poly<T, N> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = PolyRepr::deserialize(deserializer)?;
+            unpack(&repr.coeffs, repr.bits).map(Poly::new)
+        }
+    }
+
+    impl<T: Integer + Copy + NumCast, const N: usize> Serialize for ModPoly<T, N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let bits = coefficient_bits(self.modulo);
+
+            let mut state = serializer.serialize_struct("ModPoly", 2)?;
+            state.serialize_field("modulo", &Wide(self.modulo.to_i128().unwrap()))?;
+            state.serialize_field("coeffs", &Bytes(pack_bits(&to_i128(&self.coeffs), bits)))?;
+            state.end()
+        }
+    }
+
+    impl<'de, T: Integer + Copy + NumCast, const N: usize> Deserialize<'de> for ModPoly<T, N> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = ModPolyRepr::deserialize(deserializer)?;
+            let modulo = T::from(repr.modulo.0)
+                .filter(|&q| q > T::one())
+                .ok_or_else(|| de::Error::custom("invalid modulus"))?;
+
+            let coeffs: [T; N] = unpack(&repr.coeffs, coefficient_bits(modulo))?;
+            if coeffs.iter().any(|&x| !is_in_range(x, modulo)) {
+                return Err(de::Error::custom("coefficient is outside (-q/2, q/2]"));
+            }
+            Ok(ModPoly::new(coeffs, modulo))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ckks::{
+        self,
+        testing::{Context, Z},
+    };
+    use num_complex::Complex64;
+
+    const N: usize = 8;
+
+    #[test]
+    fn roundtrip() {
+        let context = Context::<N>::small(&[]);
+        let params = &context.evaluator.params;
+
+        let secret_key = SecretKey::from_bytes(&context.secret_key.to_bytes(params), params);
+        assert_eq!(
+            secret_key.unwrap().expose_secret().coeffs,
+            context.secret_key.expose_secret().coeffs
+        );
+        let public_key = PublicKey::from_bytes(&context.public_key.to_bytes(params), params);
+        assert_eq!(public_key.unwrap().a.coeffs, context.public_key.a.coeffs);
+        let evaluation_key =
+            EvaluationKey::from_bytes(&context.evaluation_key.to_bytes(params), params).unwrap();
+        assert_eq!(evaluation_key.b.coeffs, context.evaluation_key.b.coeffs);
+        assert_eq!(evaluation_key.b.modulo, context.evaluation_key.b.modulo);
+
+        let ciphertext = context
+            .evaluator
+            .rescale(context.encrypt(&Z) * context.encrypt(&Z));
+        let bytes = ciphertext.to_bytes(params).unwrap();
+        // header + 成分 (slots, encoding, 数) + 2 * N 係数 * ⌈log2 q_2⌉ = 52 ビット
+        assert_eq!(bytes.len(), HEADER_LEN + 6 + 2 * N * 52 / 8);
+        let loaded = Ciphertext::from_bytes(&bytes, params, evaluation_key).unwrap();
+        assert_eq!(loaded.scale, ciphertext.scale);
+        assert_eq!(loaded.c[1].coeffs, ciphertext.c[1].coeffs);
+        let expected = Z.map(|z| z * z);
+        // 500 回で最小 9.9 bits、中央値 11.4 bits
+        assert!(ckks::precision_bits(&expected, &context.decrypt(&loaded)) > 9.0);
+
+        let plaintext = ckks::encode_real::<i128, N>(&[0.5, -1.5], 1024.0);
+        let loaded = Plaintext::from_bytes(&plaintext.to_bytes(params, 0), params).unwrap();
+        assert_eq!(loaded.m.coeffs, plaintext.m.coeffs);
+        assert_eq!(loaded.slots, 2);
+        assert_eq!(loaded.encoding, Encoding::Real);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let context = Context::<N>::small(&[]);
+        let params = &context.evaluator.params;
+        let bytes = context.public_key.to_bytes(params);
+        let load = |bytes: &[u8]| PublicKey::<i128, N>::from_bytes(bytes, params).err();
+
+        assert_eq!(
+            load(&bytes[..bytes.len() - 1]),
+            Some(FormatError::Truncated {
+                needed: bytes.len() - HEADER_LEN,
+                available: bytes.len() - HEADER_LEN - 1
+            })
+        );
+        assert_eq!(
+            load(&[bytes.as_slice(), &[0]].concat()),
+            Some(FormatError::TrailingBytes { extra: 1 })
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert_eq!(load(&corrupted), Some(FormatError::BadMagic));
+        let mut corrupted = bytes.clone();
+        corrupted[4] = 2;
+        assert_eq!(load(&corrupted), Some(FormatError::UnsupportedVersion(2)));
+        let mut corrupted = bytes.clone();
+        corrupted[26] = 4;
+        assert_eq!(
+            load(&corrupted),
+            Some(FormatError::InvalidLevel { level: 4, limit: 3 })
+        );
+
+        assert_eq!(
+            SecretKey::<i128, N>::from_bytes(&bytes, params).err(),
+            Some(FormatError::WrongKind {
+                expected: Kind::SecretKey,
+                found: Kind::PublicKey as u8
+            })
+        );
+        let other = Params::new(3, 1 << 16, 1 << 20, 1 << 17);
+        assert!(matches!(
+            PublicKey::<i128, N>::from_bytes(&bytes, &other),
+            Err(FormatError::ParamsMismatch { .. })
+        ));
+
+        // 鍵の KeyId は 0 にならない
+        let mut corrupted = bytes.clone();
+        corrupted[14..22].fill(0);
+        assert_eq!(
+            load(&corrupted),
+            Some(FormatError::InvalidKeyId(KeyId::default()))
+        );
+
+        // 評価鍵の法は P q_L だけ (P^2 q_L の鍵は読まない)
+        let modulo = params.scale * params.scale * params.modulo(params.limit);
+        let evaluation_key = EvaluationKey::generate(&context.secret_key, modulo, params.scale);
+        assert_eq!(
+            EvaluationKey::<i128, N>::from_bytes(&evaluation_key.to_bytes(params), params).err(),
+            Some(FormatError::InvalidModulus)
+        );
+    }
+
+    #[test]
+    fn unserializable_ciphertexts() {
+        let context = Context::<N>::small(&[]);
+        let params = &context.evaluator.params;
+        let ciphertext = context.encrypt(&[Complex64::new(0.5, 0.0); 4]);
+
+        // 法の列にない法
+        let off_chain = Ciphertext {
+            c: ciphertext.c.iter().map(|c| c.reduce(12345)).collect(),
+            ..ciphertext.clone()
+        };
+        assert_eq!(
+            off_chain.to_bytes(params).err(),
+            Some(FormatError::ModulusNotInChain)
+        );
+
+        // 成分の数は u8 に収まらなければならない
+        let too_many = Ciphertext {
+            c: vec![ciphertext.c[0]; 256],
+            ..ciphertext
+        };
+        assert_eq!(
+            too_many.to_bytes(params).err(),
+            Some(FormatError::InvalidComponents(256))
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_coefficients() {
+        let params = Params::<i128>::new(3, 1000, 1000, 1000);
+        let plaintext = ckks::encode::<i128, N>(&[Complex64::new(0.1, 0.0); 4], 1000.0);
+        let mut bytes = plaintext.to_bytes(&params, 0);
+
+        // ⌈log2 1000⌉ = 10 ビットの最初の係数を 511 (> q0/2) にする
+        let offset = HEADER_LEN + 5;
+        bytes[offset] = 0xff;
+        bytes[offset + 1] = (bytes[offset + 1] & !0b11) | 0b01;
+        assert_eq!(
+            Plaintext::<i128, N>::from_bytes(&bytes, &params).err(),
+            Some(FormatError::CoefficientOutOfRange { index: 0 })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let context = Context::<N>::small(&[]);
+        let params = &context.evaluator.params;
+        let ciphertext = context.encrypt(&Z);
+
+        let json = serde_json::to_string(&ciphertext).unwrap();
+        // 配列は成分の列 c だけで、係数は詰めた 16 進文字列になる
+        assert_eq!(json.matches('[').count(), 1);
+        let loaded: Ciphertext<i128, N> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.to_bytes(params), ciphertext.to_bytes(params));
+        // 500 回で最小 10.1 bits、中央値 11.4 bits
+        assert!(ckks::precision_bits(&Z, &context.decrypt(&loaded)) > 9.0);
+
+        // 別の鍵一式の評価鍵と組み合わせたものは読めない
+        let other = Context::<N>::new(params.clone(), &[]);
+        let mismatched = Ciphertext {
+            evaluation_key: other.evaluation_key,
+            ..ciphertext.clone()
+        };
+        let json = serde_json::to_string(&mismatched).unwrap();
+        let error = serde_json::from_str::<Ciphertext<i128, N>>(&json).unwrap_err();
+        assert!(error.to_string().contains("does not belong to key set"));
+
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&ciphertext, &mut cbor).unwrap();
+        assert!(cbor.len() < json.len());
+        let loaded: Ciphertext<i128, N> = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert_eq!(loaded.to_bytes(params), ciphertext.to_bytes(params));
+
+        let json = serde_json::to_string(&context.secret_key).unwrap();
+        let secret_key: SecretKey<i128, N> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            secret_key.expose_secret().coeffs,
+            context.secret_key.expose_secret().coeffs
+        );
+
+        let plaintext = ckks::encode::<i128, N>(&Z, 1024.0);
+        let json = serde_json::to_string(&plaintext).unwrap();
+        let loaded: Plaintext<i128, N> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.m.coeffs, plaintext.m.coeffs);
+
+        let json = serde_json::to_string(params).unwrap();
+        let loaded: Params<i128> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.fingerprint(), params.fingerprint());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_extreme_coefficients() {
+        let poly = Poly::new([
+            i128::MAX,
+            i128::MIN,
+            -1,
+            0,
+            1,
+            i128::MIN + 1,
+            1 << 126,
+            -(1 << 126),
+        ]);
+        let json = serde_json::to_string(&poly).unwrap();
+        let loaded: Poly<i128, 8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.coeffs, poly.coeffs);
+
+        let poly = Poly::new([i64::MAX, i64::MIN, -1, 0]);
+        let json = serde_json::to_string(&poly).unwrap();
+        let loaded: Poly<i64, 4> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.coeffs, poly.coeffs);
+
+        // i64 に収まらない係数はエラー
+        let json = serde_json::to_string(&Poly::new([i128::MAX, 0, 0, 0])).unwrap();
+        assert!(serde_json::from_str::<Poly<i64, 4>>(&json).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_bad_coefficients() {
+        // ⌈log2 1000⌉ = 10 ビット × 4 係数 = 5 バイト、最初の係数 511 は q/2 を超える
+        let json = r#"{"modulo":"1000","coeffs":"ff01000000"}"#;
+        let error = serde_json::from_str::<ModPoly<i64, 4>>(json).unwrap_err();
+        assert!(error.to_string().contains("outside"));
+
+        let json = r#"{"modulo":"1000","coeffs":"0000"}"#;
+        assert!(serde_json::from_str::<ModPoly<i64, 4>>(json).is_err());
+        let json = r#"{"modulo":"1000","coeffs":"zz00000000"}"#;
+        assert!(serde_json::from_str::<ModPoly<i64, 4>>(json).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use super::{
+    ciphertext::Ciphertext,
+    decode, decrypt,
+    encoder::Encoder,
+    encrypt,
+    evaluator::Evaluator,
+    generate_galois_keys, generate_keys_for,
+    keys::{EvaluationKey, PublicKey, SecretKey},
+    params::Params,
+};
+use num_complex::Complex64;
+use std::{hint::black_box, time::Instant};
+
+// テストで暗号化する 4 スロットの値
+pub(crate) const Z: [Complex64; 4] = [
+    Complex64::new(0.5, -0.25),
+    Complex64::new(-0.75, 0.1),
+    Complex64::new(0.3, 0.6),
+    Complex64::new(-0.2, -0.9),
+];
+
+// L = 3, p = 2^16, q0 = 2^20, P = 2^16 (P*q_L = 2^84)
+pub(crate) fn small_params() -> Params<i128> {
+    Params::new(3, 1 << 16, 1 << 20, 1 << 16)
+}
+
+// テスト用の鍵一式 (法 P*q_L は i128 に収まるように選ぶ)
+pub(crate) struct Context<const N: usize> {
+    pub public_key: PublicKey<i128, N>,
+    pub secret_key: SecretKey<i128, N>,
+    pub evaluation_key: EvaluationKey<i128, N>,
+    pub evaluator: Evaluator<i128, N>,
+}
+
+impl<const N: usize> Context<N>
+where
+    [(); N / 2]:,
+{
+    pub fn new(params: Params<i128>, elements: &[usize]) -> Self {
+        let (public_key, secret_key, evaluation_key) = generate_keys_for(&params);
+        let galois_keys = generate_galois_keys(&secret_key, &params, elements);
+
+        Self {
+            public_key,
+            secret_key,
+            evaluation_key,
+            evaluator: Evaluator::new(params, galois_keys),
+        }
+    }
+
+    // small_params の鍵一式
+    pub fn small(elements: &[usize]) -> Self {
+        Self::new(small_params(), elements)
+    }
+
+    pub fn encrypt(&self, z: &[Complex64]) -> Ciphertext<i128, N> {
+        let params = &self.evaluator.params;
+        let plaintext = Encoder::at_level(params, params.limit).encode(z).unwrap();
+        encrypt(plaintext, self.public_key, self.evaluation_key)
+    }
+
+    pub fn decrypt(&self, ciphertext: &Ciphertext<i128, N>) -> Vec<Complex64> {
+        decode(decrypt(ciphertext.clone(), &self.secret_key))
+    }
+}
+
+// dudect 風の定数時間テスト
+// 固定入力 (class 0) とランダム入力 (class 1) を乱順に混ぜて f の実行時間を測り、
+// 上位 10% を外れ値として捨てた 2 組の平均の差の Welch の t 値を返す
+// |t| が 4.5 を超えたら実行時間が入力に依存していると見なす (release で走らせる)
+pub(crate) fn timing_t<I, R>(
+    samples: usize,
+    fixed: impl Fn() -> I,
+    random: impl Fn() -> I,
+    f: impl Fn(I) -> R,
+) -> f64 {
+    let inputs: Vec<(usize, I)> = (0..samples)
+        .map(|_| {
+            let class = rand::random_range(0..2);
+            let input = if class == 0 { fixed() } else { random() };
+            (class, input)
+        })
+        .collect();
+
+    let mut times: Vec<(usize, f64)> = inputs
+        .into_iter()
+        .map(|(class, input)| {
+            let start = Instant::now();
+            black_box(f(black_box(input)));
+            (class, start.elapsed().as_nanos() as f64)
+        })
+        .collect();
+
+    let mut sorted: Vec<f64> = times.iter().map(|&(_, t)| t).collect();
+    sorted.sort_by(f64::total_cmp);
+    let threshold = sorted[samples * 9 / 10];
+    times.retain(|&(_, t)| t <= threshold);
+
+    let [(n0, m0, v0), (n1, m1, v1)] = [0, 1].map(|class| {
+        let xs: Vec<f64> = times
+            .iter()
+            .filter(|&&(c, _)| c == class)
+            .map(|&(_, t)| t)
+            .collect();
+        let n = xs.len() as f64;
+        let mean = xs.iter().sum::<f64>() / n;
+        let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (n, mean, var)
+    });
+
+    (m0 - m1) / (v0 / n0 + v1 / n1).sqrt()
+}